@@ -0,0 +1,211 @@
+use crate::Result;
+
+use failure::ResultExt;
+
+use std::{
+  io::{Read, Write},
+  path::{Component, Path, PathBuf},
+};
+
+pub mod sarc;
+pub mod yaz0;
+
+pub use self::sarc::Sarc;
+
+/// The extensions of files that are treated as archives. Files ending in `.zs` are only archives
+/// if they are zstd-compressed SARCs, such as `Msg_USen.Product.100.sarc.zs`.
+pub const EXTENSIONS: &[&str] = &["sarc", "ssarc", "zs"];
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const ZSTD_LEVEL: i32 = 16;
+/// The name of the dictionary for `.zs` files inside of Tears of the Kingdom's `ZsDic.pack.zs`.
+const ZSTD_DICT_NAME: &str = "zs.zsdic";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+  None,
+  Yaz0,
+  /// Used by Tears of the Kingdom.
+  Zstd,
+}
+
+impl Compression {
+  /// Guess the compression of a new archive from its extension.
+  pub fn from_path(path: &Path) -> Self {
+    match path.extension().and_then(std::ffi::OsStr::to_str) {
+      Some("ssarc") => Compression::Yaz0,
+      Some("zs") => Compression::Zstd,
+      _ => Compression::None,
+    }
+  }
+
+  pub fn compress(self, data: Vec<u8>) -> Result<Vec<u8>> {
+    self.compress_with_dict(data, None)
+  }
+
+  /// Compress `data`, using the zstd dictionary `dict` if this is zstd compression.
+  pub fn compress_with_dict(self, data: Vec<u8>, dict: Option<&[u8]>) -> Result<Vec<u8>> {
+    let compressed = match (self, dict) {
+      (Compression::None, _) => data,
+      (Compression::Yaz0, _) => self::yaz0::compress(&data),
+      (Compression::Zstd, None) => zstd::stream::encode_all(&data[..], ZSTD_LEVEL).with_context(|_| "could not compress zstd data")?,
+      (Compression::Zstd, Some(dict)) => {
+        let mut encoder = zstd::stream::write::Encoder::with_dictionary(Vec::new(), ZSTD_LEVEL, dict)
+          .with_context(|_| "could not load zstd dictionary")?;
+        encoder.write_all(&data).with_context(|_| "could not compress zstd data")?;
+        encoder.finish().with_context(|_| "could not compress zstd data")?
+      },
+    };
+    Ok(compressed)
+  }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+  let extension = |p: &Path| p.extension().and_then(std::ffi::OsStr::to_str).map(ToString::to_string);
+  match extension(path) {
+    Some(ref ext) if ext == "zs" => path.file_stem()
+      .map(|stem| extension(Path::new(stem)).map(|e| e == "sarc").unwrap_or(false))
+      .unwrap_or(false),
+    Some(ext) => EXTENSIONS.contains(&ext.as_str()),
+    None => false,
+  }
+}
+
+/// The path of the directory an archive's files are unpacked into: the archive's path without
+/// its extensions.
+pub fn unpacked_path(path: &Path) -> PathBuf {
+  let path = match path.extension().and_then(std::ffi::OsStr::to_str) {
+    Some("zs") => path.with_extension(""),
+    _ => path.to_path_buf(),
+  };
+  path.with_extension("")
+}
+
+/// Open an archive, decompressing it first if it is compressed.
+pub fn open(data: &[u8]) -> Result<(Sarc, Compression)> {
+  open_with_dict(data, None)
+}
+
+/// Open an archive, decompressing it first if it is compressed. zstd data is decompressed with the
+/// dictionary `dict` if one is given.
+pub fn open_with_dict(data: &[u8], dict: Option<&[u8]>) -> Result<(Sarc, Compression)> {
+  if data.starts_with(self::yaz0::MAGIC) {
+    let decompressed = self::yaz0::decompress(data).with_context(|_| "could not decompress yaz0 data")?;
+    let sarc = Sarc::parse(&decompressed).with_context(|_| "could not parse sarc")?;
+    return Ok((sarc, Compression::Yaz0));
+  }
+
+  if data.starts_with(ZSTD_MAGIC) {
+    let decompressed = match dict {
+      Some(dict) => {
+        let mut decoder = zstd::stream::read::Decoder::with_dictionary(data, dict)
+          .with_context(|_| "could not load zstd dictionary")?;
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).with_context(|_| "could not decompress zstd data")?;
+        decompressed
+      },
+      // archives compressed with a dictionary cannot be read without it
+      None => zstd::stream::decode_all(data)
+        .with_context(|_| "could not decompress zstd data (pass the dictionary it was compressed with)")?,
+    };
+    let sarc = Sarc::parse(&decompressed).with_context(|_| "could not parse sarc")?;
+    return Ok((sarc, Compression::Zstd));
+  }
+
+  let sarc = Sarc::parse(data).with_context(|_| "could not parse sarc")?;
+  Ok((sarc, Compression::None))
+}
+
+/// Serialise an archive, compressing it if required.
+pub fn write(sarc: &Sarc, compression: Compression) -> Result<Vec<u8>> {
+  write_with_dict(sarc, compression, None)
+}
+
+/// Serialise an archive, compressing it if required. zstd data is compressed with the dictionary
+/// `dict` if one is given.
+pub fn write_with_dict(sarc: &Sarc, compression: Compression, dict: Option<&[u8]>) -> Result<Vec<u8>> {
+  let data = sarc.to_bytes().with_context(|_| "could not write sarc")?;
+  compression.compress_with_dict(data, dict)
+}
+
+/// Read a zstd dictionary. `data` is either a raw dictionary or an archive of dictionaries, such as
+/// Tears of the Kingdom's `ZsDic.pack.zs`, in which case its `zs.zsdic` is used.
+pub fn read_dictionary(data: &[u8]) -> Result<Vec<u8>> {
+  if !data.starts_with(ZSTD_MAGIC) && !data.starts_with(b"SARC") {
+    return Ok(data.to_vec());
+  }
+  let (mut sarc, _) = open(data).with_context(|_| "could not open dictionary archive")?;
+  match sarc.files.swap_remove(ZSTD_DICT_NAME) {
+    Some(dict) => Ok(dict),
+    None => failure::bail!("dictionary archive does not contain {}", ZSTD_DICT_NAME),
+  }
+}
+
+/// Convert a relative path into the name a file would have inside of an archive.
+pub fn file_name(path: &Path) -> String {
+  path.components()
+    .map(|c| c.as_os_str().to_string_lossy())
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// The path a file named `name` inside of an archive is unpacked to under `dest`. Names come from
+/// the archive, so any that would escape `dest`, such as absolute paths or ones containing `..`,
+/// are rejected.
+pub fn entry_path(dest: &Path, name: &str) -> Result<PathBuf> {
+  let name_path = Path::new(name);
+  if name.is_empty() || !name_path.components().all(|c| matches!(c, Component::Normal(_))) {
+    failure::bail!("archive entry {} is not a relative path inside of the archive", name);
+  }
+  Ok(dest.join(name_path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use byteordered::Endianness;
+
+  const DICT: &[u8] = b"a raw content dictionary that shares text with the archive: Msg_USen.Product.100";
+
+  fn sarc() -> Sarc {
+    let mut sarc = Sarc::new(Endianness::Little);
+    sarc.files.insert("Msg_USen.Product.100/ActorType/Armor.msbt".into(), b"MsgStdBn".to_vec());
+    sarc
+  }
+
+  #[test]
+  fn zstd_round_trips_with_dictionary() {
+    let data = write_with_dict(&sarc(), Compression::Zstd, Some(DICT)).unwrap();
+    assert!(data.starts_with(ZSTD_MAGIC));
+
+    let (opened, compression) = open_with_dict(&data, Some(DICT)).unwrap();
+    assert_eq!(compression, Compression::Zstd);
+    assert_eq!(opened.files, sarc().files);
+  }
+
+  #[test]
+  fn dictionary_is_taken_from_archive() {
+    let mut dicts = Sarc::new(Endianness::Little);
+    dicts.files.insert("pack.zsdic".into(), b"pack".to_vec());
+    dicts.files.insert(ZSTD_DICT_NAME.into(), DICT.to_vec());
+    let data = write(&dicts, Compression::Zstd).unwrap();
+
+    assert_eq!(read_dictionary(&data).unwrap(), DICT);
+    assert_eq!(read_dictionary(DICT).unwrap(), DICT);
+  }
+
+  #[test]
+  fn entry_paths_stay_inside_dest() {
+    let dest = Path::new("out");
+    assert_eq!(
+      entry_path(dest, "Msg_USen.Product.100/ActorType/Armor.msbt").unwrap(),
+      dest.join("Msg_USen.Product.100/ActorType/Armor.msbt"),
+    );
+    assert!(entry_path(dest, "../evil.msbt").is_err());
+    assert!(entry_path(dest, "ActorType/../../evil.msbt").is_err());
+    assert!(entry_path(dest, "./evil.msbt").is_err());
+    assert!(entry_path(dest, "/tmp/evil.msbt").is_err());
+    assert!(entry_path(dest, "").is_err());
+  }
+}
@@ -0,0 +1,259 @@
+use crate::Result;
+
+use byteordered::{Endian, Endianness};
+
+use failure::ResultExt;
+
+use indexmap::IndexMap;
+
+const SARC_MAGIC: &[u8] = b"SARC";
+const SFAT_MAGIC: &[u8] = b"SFAT";
+const SFNT_MAGIC: &[u8] = b"SFNT";
+
+const SARC_HEADER_LEN: usize = 0x14;
+const SFAT_HEADER_LEN: usize = 0x0C;
+const SFAT_NODE_LEN: usize = 0x10;
+const SFNT_HEADER_LEN: usize = 0x08;
+
+const HASH_KEY: u32 = 0x65;
+const VERSION: u16 = 0x0100;
+const DATA_ALIGNMENT: usize = 0x80;
+
+/// A SARC archive. Files are kept in the order they appeared in the archive.
+#[derive(Debug)]
+pub struct Sarc {
+  pub endianness: Endianness,
+  pub files: IndexMap<String, Vec<u8>>,
+}
+
+impl Sarc {
+  pub fn new(endianness: Endianness) -> Self {
+    Sarc {
+      endianness,
+      files: IndexMap::new(),
+    }
+  }
+
+  pub fn parse(data: &[u8]) -> Result<Self> {
+    if data.len() < SARC_HEADER_LEN || !data.starts_with(SARC_MAGIC) {
+      failure::bail!("invalid sarc: bad magic");
+    }
+
+    let endianness = match &data[6..8] {
+      [0xFE, 0xFF] => Endianness::Big,
+      [0xFF, 0xFE] => Endianness::Little,
+      x => failure::bail!("invalid sarc: bad byte order mark {:?}", x),
+    };
+
+    let header_len = endianness.read_u16(&data[4..6])? as usize;
+    let data_offset = endianness.read_u32(&data[12..16]).with_context(|_| "could not read data offset")? as usize;
+
+    let sfat = data.get(header_len..).ok_or_else(|| failure::format_err!("invalid sarc: missing sfat"))?;
+    if sfat.len() < SFAT_HEADER_LEN || !sfat.starts_with(SFAT_MAGIC) {
+      failure::bail!("invalid sarc: bad sfat magic");
+    }
+    let node_count = endianness.read_u16(&sfat[6..8]).with_context(|_| "could not read node count")? as usize;
+
+    let sfnt_offset = header_len + SFAT_HEADER_LEN + node_count * SFAT_NODE_LEN;
+    let sfnt = data.get(sfnt_offset..).ok_or_else(|| failure::format_err!("invalid sarc: missing sfnt"))?;
+    if sfnt.len() < SFNT_HEADER_LEN || !sfnt.starts_with(SFNT_MAGIC) {
+      failure::bail!("invalid sarc: bad sfnt magic");
+    }
+    let names = &sfnt[SFNT_HEADER_LEN..];
+
+    let mut files = IndexMap::with_capacity(node_count);
+    for i in 0..node_count {
+      let node = &sfat[SFAT_HEADER_LEN + i * SFAT_NODE_LEN..SFAT_HEADER_LEN + (i + 1) * SFAT_NODE_LEN];
+      let attributes = endianness.read_u32(&node[4..8]).with_context(|_| "could not read file attributes")?;
+      let start = endianness.read_u32(&node[8..12]).with_context(|_| "could not read file start")? as usize;
+      let end = endianness.read_u32(&node[12..16]).with_context(|_| "could not read file end")? as usize;
+
+      if attributes & 0xFF00_0000 == 0 {
+        failure::bail!("invalid sarc: file {} has no name", i);
+      }
+      let name_offset = (attributes & 0x00FF_FFFF) as usize * 4;
+      let name_bytes = names.get(name_offset..)
+        .ok_or_else(|| failure::format_err!("invalid sarc: name offset out of bounds for file {}", i))?;
+      let name_len = name_bytes.iter().position(|&b| b == 0).unwrap_or(name_bytes.len());
+      let name = String::from_utf8(name_bytes[..name_len].to_vec())
+        .with_context(|_| format!("could not parse name of file {}", i))?;
+
+      let file = data.get(data_offset + start..data_offset + end)
+        .ok_or_else(|| failure::format_err!("invalid sarc: data out of bounds for {}", name))?;
+      if files.contains_key(&name) {
+        failure::bail!("invalid sarc: more than one file is named {}", name);
+      }
+      files.insert(name, file.to_vec());
+    }
+
+    Ok(Sarc {
+      endianness,
+      files,
+    })
+  }
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    let endianness = self.endianness;
+
+    let mut nodes: Vec<(u32, &str, &[u8])> = self.files
+      .iter()
+      .map(|(name, data)| (hash(name), name.as_str(), data.as_slice()))
+      .collect();
+    // games look files up by a binary search on the hash, so names whose hashes collide are
+    // ordered by name to keep the layout stable
+    nodes.sort_by_key(|&(hash, name, _)| (hash, name));
+
+    let mut names = Vec::new();
+    let mut name_offsets = Vec::with_capacity(nodes.len());
+    for &(_, name, _) in &nodes {
+      name_offsets.push(names.len());
+      names.extend_from_slice(name.as_bytes());
+      names.push(0);
+      pad(&mut names, 4);
+    }
+
+    let tables_len = SARC_HEADER_LEN
+      + SFAT_HEADER_LEN
+      + nodes.len() * SFAT_NODE_LEN
+      + SFNT_HEADER_LEN
+      + names.len();
+    let data_offset = align(tables_len, DATA_ALIGNMENT);
+
+    let mut file_data = Vec::new();
+    let mut ranges = Vec::with_capacity(nodes.len());
+    for &(_, _, data) in &nodes {
+      pad(&mut file_data, DATA_ALIGNMENT);
+      let start = file_data.len();
+      file_data.extend_from_slice(data);
+      ranges.push((start, file_data.len()));
+    }
+
+    let mut buf = Vec::with_capacity(data_offset + file_data.len());
+
+    buf.extend_from_slice(SARC_MAGIC);
+    endianness.write_u16(&mut buf, SARC_HEADER_LEN as u16).with_context(|_| "could not write header length")?;
+    endianness.write_u16(&mut buf, 0xFEFF).with_context(|_| "could not write byte order mark")?;
+    endianness.write_u32(&mut buf, (data_offset + file_data.len()) as u32).with_context(|_| "could not write file size")?;
+    endianness.write_u32(&mut buf, data_offset as u32).with_context(|_| "could not write data offset")?;
+    endianness.write_u16(&mut buf, VERSION).with_context(|_| "could not write version")?;
+    endianness.write_u16(&mut buf, 0).with_context(|_| "could not write reserved bytes")?;
+
+    buf.extend_from_slice(SFAT_MAGIC);
+    endianness.write_u16(&mut buf, SFAT_HEADER_LEN as u16).with_context(|_| "could not write sfat header length")?;
+    endianness.write_u16(&mut buf, nodes.len() as u16).with_context(|_| "could not write node count")?;
+    endianness.write_u32(&mut buf, HASH_KEY).with_context(|_| "could not write hash key")?;
+    for (i, &(hash, _, _)) in nodes.iter().enumerate() {
+      let attributes = 0x0100_0000 | (name_offsets[i] / 4) as u32;
+      endianness.write_u32(&mut buf, hash).with_context(|_| "could not write name hash")?;
+      endianness.write_u32(&mut buf, attributes).with_context(|_| "could not write file attributes")?;
+      endianness.write_u32(&mut buf, ranges[i].0 as u32).with_context(|_| "could not write file start")?;
+      endianness.write_u32(&mut buf, ranges[i].1 as u32).with_context(|_| "could not write file end")?;
+    }
+
+    buf.extend_from_slice(SFNT_MAGIC);
+    endianness.write_u16(&mut buf, SFNT_HEADER_LEN as u16).with_context(|_| "could not write sfnt header length")?;
+    endianness.write_u16(&mut buf, 0).with_context(|_| "could not write reserved bytes")?;
+    buf.extend_from_slice(&names);
+
+    buf.resize(data_offset, 0);
+    buf.extend_from_slice(&file_data);
+
+    Ok(buf)
+  }
+}
+
+fn hash(name: &str) -> u32 {
+  name.bytes().fold(0, |hash, b| hash.wrapping_mul(HASH_KEY).wrapping_add(u32::from(b)))
+}
+
+fn align(n: usize, alignment: usize) -> usize {
+  n + (alignment - n % alignment) % alignment
+}
+
+fn pad(buf: &mut Vec<u8>, alignment: usize) {
+  let len = align(buf.len(), alignment);
+  buf.resize(len, 0);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sarc(endianness: Endianness) -> Sarc {
+    let mut sarc = Sarc::new(endianness);
+    sarc.files.insert("ActorType/Armor.msbt".into(), b"MsgStdBn armor".to_vec());
+    sarc.files.insert("EventFlowMsg/Npc.msbt".into(), vec![0xAB; 0x90]);
+    sarc
+  }
+
+  #[test]
+  fn sarc_round_trips() {
+    for &endianness in &[Endianness::Little, Endianness::Big] {
+      let bytes = sarc(endianness).to_bytes().unwrap();
+      let parsed = Sarc::parse(&bytes).unwrap();
+      assert_eq!(parsed.endianness, endianness);
+      assert_eq!(parsed.files, sarc(endianness).files);
+      assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+  }
+
+  #[test]
+  fn header_and_nodes_are_laid_out() {
+    let bytes = sarc(Endianness::Little).to_bytes().unwrap();
+    assert_eq!(&bytes[..8], b"SARC\x14\x00\xFF\xFE");
+    assert_eq!(&bytes[SARC_HEADER_LEN..SARC_HEADER_LEN + 4], SFAT_MAGIC);
+
+    // file data starts aligned
+    let data_offset = Endianness::Little.read_u32(&bytes[12..16]).unwrap() as usize;
+    assert_eq!(data_offset % DATA_ALIGNMENT, 0);
+
+    // nodes are sorted by the hash of their name
+    let nodes = SARC_HEADER_LEN + SFAT_HEADER_LEN;
+    let hashes: Vec<u32> = (0..2)
+      .map(|i| Endianness::Little.read_u32(&bytes[nodes + i * SFAT_NODE_LEN..]).unwrap())
+      .collect();
+    let mut sorted = hashes.clone();
+    sorted.sort();
+    assert_eq!(hashes, sorted);
+  }
+
+  #[test]
+  fn names_are_hashed() {
+    assert_eq!(hash(""), 0);
+    assert_eq!(hash("a"), 0x61);
+    assert_eq!(hash("ab"), 0x61 * HASH_KEY + 0x62);
+  }
+
+  #[test]
+  fn bad_magic_is_rejected() {
+    let mut bytes = sarc(Endianness::Little).to_bytes().unwrap();
+    bytes[0] = b'X';
+    assert!(Sarc::parse(&bytes).is_err());
+  }
+
+  #[test]
+  fn colliding_hashes_are_sorted_by_name() {
+    let (first, second) = ("itpdcnfu.msbt", "ntrintqi.msbt");
+    assert_eq!(hash(first), hash(second));
+
+    for &order in &[[first, second], [second, first]] {
+      let mut sarc = Sarc::new(Endianness::Little);
+      for &name in &order {
+        sarc.files.insert(name.into(), name.as_bytes().to_vec());
+      }
+      let parsed = Sarc::parse(&sarc.to_bytes().unwrap()).unwrap();
+      assert_eq!(parsed.files.keys().collect::<Vec<_>>(), [first, second]);
+    }
+  }
+
+  #[test]
+  fn duplicate_names_are_rejected() {
+    let mut sarc = Sarc::new(Endianness::Little);
+    sarc.files.insert("ab".into(), vec![1]);
+    sarc.files.insert("ac".into(), vec![2]);
+    let mut bytes = sarc.to_bytes().unwrap();
+    let second = bytes.windows(3).position(|w| w == b"ac\0").unwrap();
+    bytes[second + 1] = b'b';
+    assert!(Sarc::parse(&bytes).is_err());
+  }
+}
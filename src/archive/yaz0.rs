@@ -0,0 +1,194 @@
+use crate::Result;
+
+use byteordered::{Endian, Endianness};
+
+use failure::ResultExt;
+
+use std::collections::HashMap;
+
+pub const MAGIC: &[u8] = b"Yaz0";
+
+const HEADER_LEN: usize = 0x10;
+const MAX_DISTANCE: usize = 0x1000;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 0xFF + 0x12;
+/// How many earlier positions to check when looking for a match.
+const MAX_CANDIDATES: usize = 64;
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+  if data.len() < HEADER_LEN || !data.starts_with(MAGIC) {
+    failure::bail!("invalid yaz0: bad magic");
+  }
+
+  let size = Endianness::Big.read_u32(&data[4..8]).with_context(|_| "could not read decompressed size")? as usize;
+  // the size comes from the file, so only reserve what the data could plausibly expand to
+  let mut out = Vec::with_capacity(size.min(data.len().saturating_mul(8)));
+
+  let mut src = HEADER_LEN;
+  let mut next_byte = || -> Result<u8> {
+    let b = data.get(src).cloned().ok_or_else(|| failure::format_err!("invalid yaz0: unexpected end of data"))?;
+    src += 1;
+    Ok(b)
+  };
+
+  let mut group_header = 0;
+  let mut bits_left = 0;
+  while out.len() < size {
+    if bits_left == 0 {
+      group_header = next_byte()?;
+      bits_left = 8;
+    }
+
+    if group_header & 0x80 != 0 {
+      out.push(next_byte()?);
+    } else {
+      let b1 = next_byte()?;
+      let b2 = next_byte()?;
+      let distance = ((usize::from(b1 & 0x0F) << 8) | usize::from(b2)) + 1;
+      let length = match b1 >> 4 {
+        0 => usize::from(next_byte()?) + 0x12,
+        n => usize::from(n) + 2,
+      };
+      if distance > out.len() {
+        failure::bail!("invalid yaz0: back-reference before start of data");
+      }
+      let start = out.len() - distance;
+      for i in 0..length.min(size - out.len()) {
+        let b = out[start + i];
+        out.push(b);
+      }
+    }
+
+    group_header <<= 1;
+    bits_left -= 1;
+  }
+
+  if out.len() != size {
+    failure::bail!("invalid yaz0: decompressed {} bytes but expected {}", out.len(), size);
+  }
+  Ok(out)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(HEADER_LEN + data.len() + data.len() / 8 + 1);
+  out.extend_from_slice(MAGIC);
+  Endianness::Big.write_u32(&mut out, data.len() as u32).expect("failed writing to vec");
+  out.extend_from_slice(&[0; 8]);
+
+  let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+  let mut pos = 0;
+
+  while pos < data.len() {
+    let header_pos = out.len();
+    out.push(0);
+
+    for bit in 0..8 {
+      if pos >= data.len() {
+        break;
+      }
+
+      let (distance, length) = find_match(data, pos, &positions);
+      let advance = if length >= MIN_MATCH {
+        let distance = distance - 1;
+        if length >= 0x12 {
+          out.push((distance >> 8) as u8);
+          out.push(distance as u8);
+          out.push((length - 0x12) as u8);
+        } else {
+          out.push((((length - 2) << 4) | (distance >> 8)) as u8);
+          out.push(distance as u8);
+        }
+        length
+      } else {
+        out[header_pos] |= 0x80 >> bit;
+        out.push(data[pos]);
+        1
+      };
+
+      for p in pos..pos + advance {
+        if p + MIN_MATCH <= data.len() {
+          positions.entry(&data[p..p + MIN_MATCH]).or_default().push(p);
+        }
+      }
+      pos += advance;
+    }
+  }
+
+  out
+}
+
+fn find_match(data: &[u8], pos: usize, positions: &HashMap<&[u8], Vec<usize>>) -> (usize, usize) {
+  if pos + MIN_MATCH > data.len() {
+    return (0, 0);
+  }
+
+  let candidates = match positions.get(&data[pos..pos + MIN_MATCH]) {
+    Some(c) => c,
+    None => return (0, 0),
+  };
+
+  let max_len = std::cmp::min(MAX_MATCH, data.len() - pos);
+  let mut best = (0, 0);
+  for &candidate in candidates.iter().rev().take(MAX_CANDIDATES) {
+    let distance = pos - candidate;
+    if distance > MAX_DISTANCE {
+      break;
+    }
+    let length = (0..max_len)
+      .take_while(|&i| data[candidate + i] == data[pos + i])
+      .count();
+    if length > best.1 {
+      best = (distance, length);
+      if length == max_len {
+        break;
+      }
+    }
+  }
+
+  best
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decompresses_back_references() {
+    // three literals, then a copy of seven bytes from three bytes back
+    let data = b"Yaz0\x00\x00\x00\x0A\x00\x00\x00\x00\x00\x00\x00\x00\xE0abc\x50\x02";
+    assert_eq!(decompress(data).unwrap(), b"abcabcabca");
+  }
+
+  #[test]
+  fn decompresses_long_back_references() {
+    // one literal, then a copy of 0x20 bytes from one byte back using the three-byte form
+    let data = b"Yaz0\x00\x00\x00\x21\x00\x00\x00\x00\x00\x00\x00\x00\x80z\x00\x00\x0E";
+    assert_eq!(decompress(data).unwrap(), vec![b'z'; 0x21]);
+  }
+
+  #[test]
+  fn compression_round_trips() {
+    let mut data = b"abcabcabca".to_vec();
+    data.extend(vec![0; 0x200]);
+    data.extend((0..=255).collect::<Vec<u8>>());
+    data.extend_from_slice(b"abcabcabca");
+
+    let compressed = compress(&data);
+    assert!(compressed.starts_with(MAGIC));
+    assert!(compressed.len() < data.len());
+    assert_eq!(decompress(&compressed).unwrap(), data);
+  }
+
+  #[test]
+  fn truncated_data_is_rejected() {
+    let data = b"Yaz0\x00\x00\x00\x0A\x00\x00\x00\x00\x00\x00\x00\x00\xE0abc";
+    assert!(decompress(data).is_err());
+    assert!(decompress(b"Yaz0").is_err());
+  }
+
+  #[test]
+  fn huge_sizes_need_the_data_to_back_them() {
+    let data = b"Yaz0\xFF\xFF\xFF\xFF\x00\x00\x00\x00\x00\x00\x00\x00\xE0abc";
+    assert!(decompress(data).is_err());
+  }
+}
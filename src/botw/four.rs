@@ -25,7 +25,7 @@ use self::{
   three::Control4_3,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Control4 {
   Zero(Control4_0),
@@ -48,7 +48,7 @@ impl MainControl for Control4 {
       1 => Control4_1::parse(header, &mut c).with_context(|_| "could not parse control subtype 1")?,
       2 => Control4_2::parse(header, &mut c).with_context(|_| "could not parse control subtype 2")?,
       3 => Control4_3::parse(header, &mut c).with_context(|_| "could not parse control subtype 3")?,
-      x => failure::bail!("unknown control 4 subtype: {}", x),
+      _ => return Control::parse_unknown(header, 4, buf),
     };
 
     Ok((
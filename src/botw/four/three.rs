@@ -12,7 +12,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control4_3 {
   field_1: u16,
 }
@@ -22,14 +22,14 @@ impl SubControl for Control4_3 {
     3
   }
 
-  fn parse(header: &Header, mut reader: &mut Cursor<&[u8]>) -> Result<Control> {
+  fn parse(header: &Header, reader: &mut Cursor<&[u8]>) -> Result<Control> {
     Ok(Control::Raw(RawControl::Four(Control4::Three(Control4_3 {
-      field_1: header.endianness().read_u16(&mut reader)?,
+      field_1: header.endianness().read_u16(reader)?,
     }))))
   }
 
-  fn write(&self, header: &Header, mut writer: &mut dyn Write) -> Result<()> {
-    header.endianness().write_u16(&mut writer, self.field_1)?;
+  fn write(&self, header: &Header, writer: &mut dyn Write) -> Result<()> {
+    header.endianness().write_u16(writer, self.field_1)?;
 
     Ok(())
   }
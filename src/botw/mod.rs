@@ -1,14 +1,18 @@
 use crate::{
   Result,
+  error::ControlError,
+  game::{ControlParser, Game, Profile, encode_text, write_code_unit},
   model::Content,
+  msbp::Value,
 };
 use byteordered::Endian;
 use failure::ResultExt;
+use indexmap::IndexMap;
 use msbt::Header;
 use serde_derive::{Deserialize, Serialize};
 use std::{
   boxed::Box,
-  io::{Cursor, Write},
+  io::{Cursor, Read, Write},
 };
 
 pub mod zero;
@@ -19,82 +23,36 @@ pub mod four;
 pub mod five;
 pub mod two_hundred_one;
 
-pub fn parse_controls(header: &Header, s: &[u8]) -> Result<Vec<Content>> {
-  let mut parts = Vec::new();
-  let mut last_was_marker = false;
-  let mut skip = 0;
-  let mut text_index = None;
+use self::two::Layout;
 
-  for i in 0..s.len() {
-    if skip > 0 {
-      skip -= 1;
-      continue;
-    }
-    if i + 1 < s.len() {
-      let chunk = &s[i..=i + 1];
-      let u = header.endianness().read_u16(chunk).with_context(|_| "could not control sequence marker")?;
-      skip += 1;
-      if last_was_marker {
-        let body = &s[i + 2..];
-        let (read, ctl) = match u {
-          0x00 => self::zero::Control0::parse(header, body).with_context(|_| "could not parse control sequence 0")?,
-          0x01 => self::one::Control1::parse(header, body).with_context(|_| "could not parse control sequence 1")?,
-          0x02 => self::two::Control2::parse(header, body).with_context(|_| "could not parse control sequence 2")?,
-          0x03 => self::three::Control3::parse(header, body).with_context(|_| "could not parse control sequence 3")?,
-          0x04 => self::four::Control4::parse(header, body).with_context(|_| "could not parse control sequence 4")?,
-          0x05 => self::five::Control5::parse(header, body).with_context(|_| "could not parse control sequence 5")?,
-          0xc9 => self::two_hundred_one::Control201::parse(header, body).with_context(|_| "could not parse control sequence 201")?,
-          x => failure::bail!("unknown control sequence: {}", x),
-        };
-        let part = Content::Control(ctl);
-        skip = read + 1;
-        parts.push(part);
-      }
-      if text_index.is_none() && !last_was_marker && u != 0x0e {
-        text_index = Some(i);
-      }
-      if u == 0x0e {
-        last_was_marker = true;
-        if let Some(text_index) = text_index {
-          let bytes: Vec<u16> = s[text_index..i]
-            .chunks(2)
-            .map(|x| header.endianness().read_u16(x)
-              .with_context(|_| "could not read bytes")
-              .map_err(Into::into))
-            .collect::<Result<_>>()?;
-          let string = String::from_utf16(&bytes).with_context(|_| "could not parse utf-16 string")?;
-          parts.push(Content::Text(string));
-        }
-        text_index = None;
-      } else {
-        last_was_marker = false;
-      }
-    }
-
-  }
+/// Parse the text and control sequences in a TXT2 string using the controls known from Breath of
+/// the Wild.
+///
+/// This is the same as [`Game::parse_controls`] with [`Game::Botw`].
+pub fn parse_controls(header: &Header, s: &[u8]) -> std::result::Result<Vec<Content>, ControlError> {
+  Game::Botw.parse_controls(header, s)
+}
 
-  if let Some(text_index) = text_index {
-    let bytes: Vec<u16> = s[text_index..]
-      .chunks(2)
-      .map(|x| header.endianness().read_u16(x)
-        .with_context(|_| "could not read bytes")
-        .map_err(Into::into))
-      .collect::<Result<_>>()?;
-    let from = if bytes[bytes.len() - 1] == 0 {
-      &bytes[..bytes.len() - 1]
-    } else {
-      &bytes
-    };
-    let string = String::from_utf16(&from).with_context(|_| "could not parse utf-16 string")?;
-    if !string.is_empty() {
-      parts.push(Content::Text(string));
-    }
+/// The control sequences of Breath of the Wild.
+#[derive(Debug)]
+pub struct Botw;
+
+impl Profile for Botw {
+  fn controls(&self) -> &[(u16, ControlParser)] {
+    &[
+      (0x00, self::zero::Control0::parse),
+      (0x01, self::one::Control1::parse),
+      (0x02, self::two::Control2::parse),
+      (0x03, self::three::Control3::parse),
+      (0x04, self::four::Control4::parse),
+      (0x05, self::five::Control5::parse),
+      (0xc9, self::two_hundred_one::Control201::parse),
+    ]
   }
 
-  Ok(parts)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum Control {
   Raw(RawControl),
@@ -104,9 +62,13 @@ pub enum Control {
   Pause(PauseKind),
   Icon { icon: Icon },
   Variable {
-    variable_kind: u16,
+    variable_kind: VariableKind,
     name: String,
   },
+  /// A variable that is not named, such as one whose value the game always knows.
+  UnnamedVariable {
+    variable_kind: VariableKind,
+  },
   Choice {
     choice_labels: Vec<u16>,
     selected_index: u8,
@@ -126,9 +88,38 @@ pub enum Control {
     options: Vec<String>,
   },
   Font { font_kind: Font },
+  /// Ends the current text box and continues in a new one.
+  PageBreak,
+  /// Ruby (furigana) shown over the next `base_chars` code units of text.
+  Ruby {
+    base_chars: u16,
+    ruby: String,
+  },
+  /// A control sequence that is not understood, kept as-is so it can be written back.
+  Unknown {
+    // "kind" is already the tag for this enum
+    #[serde(rename = "control_kind")]
+    kind: u16,
+    sub_kind: u16,
+    #[serde(with = "crate::util::serde_base64")]
+    data: Vec<u8>,
+  },
+  /// A control sequence decoded with an MSBP, with typed parameters.
+  Tag {
+    /// The control type, which is the tag group's ID.
+    group: u16,
+    /// The control subtype, which is the tag's index in its group.
+    tag: u16,
+    /// The group and tag names from the MSBP, for reference. Not used when writing.
+    name: String,
+    params: IndexMap<String, Value>,
+    /// Bytes after the parameters, usually alignment padding.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "crate::util::serde_base64")]
+    padding: Vec<u8>,
+  },
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Font {
   Normal,
@@ -154,7 +145,7 @@ impl Font {
   }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Localisation {
   Gender,
@@ -180,6 +171,104 @@ impl Localisation {
   }
 }
 
+/// The kind of value a variable holds, from the subtype of its control sequence.
+///
+/// Only the kinds whose use is known are named. The rest keep their subtype as `Unknown`. When
+/// read from an MSYT, a kind may also be given as its subtype, such as `variable_kind: 9`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+  /// A string set by the game.
+  String,
+  /// A number.
+  Number,
+  /// The player's name. This is subtype 3, which only appears without a name and is filled in by
+  /// the game; that it holds the player's name is inferred from where it is used, not verified.
+  PlayerName,
+  /// The name of an item.
+  ItemName,
+  Unknown(u16),
+}
+
+impl VariableKind {
+  const NAMES: &'static [&'static str] = &["string", "number", "player_name", "item_name", "unknown"];
+
+  pub fn as_u16(self) -> u16 {
+    match self {
+      VariableKind::String => 1,
+      VariableKind::Number => 2,
+      VariableKind::PlayerName => 3,
+      VariableKind::ItemName => 9,
+      VariableKind::Unknown(x) => x,
+    }
+  }
+
+  pub fn from_u16(u: u16) -> Self {
+    match u {
+      1 => VariableKind::String,
+      2 => VariableKind::Number,
+      3 => VariableKind::PlayerName,
+      9 => VariableKind::ItemName,
+      x => VariableKind::Unknown(x),
+    }
+  }
+}
+
+impl<'de> serde::Deserialize<'de> for VariableKind {
+  fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: serde::Deserializer<'de>,
+  {
+    deserializer.deserialize_any(VariableKindVisitor)
+  }
+}
+
+/// Reads a variable kind from its name, its subtype or `unknown: <subtype>`.
+struct VariableKindVisitor;
+
+impl<'de> serde::de::Visitor<'de> for VariableKindVisitor {
+  type Value = VariableKind;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("a variable kind or a control subtype")
+  }
+
+  fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+    if v > u64::from(u16::MAX) {
+      return Err(E::invalid_value(serde::de::Unexpected::Unsigned(v), &self));
+    }
+    Ok(VariableKind::from_u16(v as u16))
+  }
+
+  fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<Self::Value, E> {
+    if v < 0 {
+      return Err(E::invalid_value(serde::de::Unexpected::Signed(v), &self));
+    }
+    self.visit_u64(v as u64)
+  }
+
+  fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+    match v {
+      "string" => Ok(VariableKind::String),
+      "number" => Ok(VariableKind::Number),
+      "player_name" => Ok(VariableKind::PlayerName),
+      "item_name" => Ok(VariableKind::ItemName),
+      _ => Err(E::unknown_variant(v, VariableKind::NAMES)),
+    }
+  }
+
+  fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+    let kind = match map.next_key::<String>()? {
+      Some(ref key) if key == "unknown" => VariableKind::from_u16(map.next_value()?),
+      Some(key) => return Err(serde::de::Error::unknown_variant(&key, &["unknown"])),
+      None => return Err(serde::de::Error::invalid_length(0, &self)),
+    };
+    if map.next_key::<serde::de::IgnoredAny>()?.is_some() {
+      return Err(serde::de::Error::invalid_length(2, &self));
+    }
+    Ok(kind)
+  }
+}
+
 enum MainControlRef<'a> {
   Borrowed(&'a dyn MainControl),
   Owned(Box<dyn MainControl>),
@@ -188,7 +277,7 @@ enum MainControlRef<'a> {
 impl<'a> MainControl for MainControlRef<'a> {
   fn marker(&self) -> u16 {
     match *self {
-      MainControlRef::Borrowed(ref b) => b.marker(),
+      MainControlRef::Borrowed(b) => b.marker(),
       MainControlRef::Owned(ref b) => b.marker(),
     }
   }
@@ -201,16 +290,39 @@ impl<'a> MainControl for MainControlRef<'a> {
 
   fn write(&self, header: &Header, writer: &mut dyn Write) -> Result<()> {
     match *self {
-      MainControlRef::Borrowed(ref b) => b.write(header, writer),
+      MainControlRef::Borrowed(b) => b.write(header, writer),
       MainControlRef::Owned(ref b) => b.write(header, writer),
     }
   }
 }
 
 impl Control {
-  fn as_main_control(&self) -> Result<MainControlRef> {
+  /// Parse a control sequence of type `kind` that has no known structure.
+  ///
+  /// Every control sequence is followed by a subtype and the length of its parameters, so
+  /// `buf` can be read as those, followed by the parameters themselves.
+  pub(crate) fn parse_unknown(header: &Header, kind: u16, buf: &[u8]) -> Result<(usize, Control)> {
+    let mut c = Cursor::new(buf);
+    let sub_kind = header.endianness().read_u16(&mut c).with_context(|_| "could not read control subtype")?;
+    let len = header.endianness().read_u16(&mut c).with_context(|_| "could not read parameter length")?;
+    let mut data = vec![0; len as usize];
+    c.read_exact(&mut data).with_context(|_| "could not read parameters")?;
+
+    Ok((
+      c.position() as usize,
+      Control::Unknown {
+        kind,
+        sub_kind,
+        data,
+      },
+    ))
+  }
+
+  fn as_main_control(&self, header: &Header) -> Result<MainControlRef<'_>> {
     let b: Box<dyn MainControl> = match *self {
       Control::Raw(ref raw) => return Ok(MainControlRef::Borrowed(raw.as_main_control())),
+      Control::Unknown { .. } => failure::bail!("unknown controls have no main control"),
+      Control::Tag { .. } => failure::bail!("tags have no main control"),
 
       Control::SetColour { colour } => Box::new(self::zero::Control0::Three(self::zero::three::Control0_3 {
         field_1: 2,
@@ -235,11 +347,15 @@ impl Control {
           205,
         ],
       })),
-      Control::Variable { variable_kind, ref name } => Box::new(self::two::Control2::Variable(variable_kind, self::two::variable::Control2Variable {
-        field_1: name.len() as u16 * 2 + 4,
+      // the length of the parameters: the string, its length and field_3
+      Control::Variable { variable_kind, ref name } => Box::new(self::two::Control2::Variable(Layout::Variable.subtype(variable_kind)?, self::two::variable::Control2Variable {
+        field_1: encode_text(header, name).len() as u16 + 4,
         string: name.clone(),
         field_3: 0,
       })),
+      Control::UnnamedVariable { variable_kind } => Box::new(self::two::Control2::OneField(Layout::OneField.subtype(variable_kind)?, self::two::one_field::Control2OneField {
+        field_1: 0,
+      })),
       Control::Choice { ref choice_labels, selected_index, cancel_index, unknown } => {
         match choice_labels.len() + 2 {
           4 => Box::new(self::one::Control1::Four(self::one::four::Control1_4 {
@@ -279,7 +395,7 @@ impl Control {
         field_1: unknown.clone(),
       })),
       Control::Animation { ref name } => Box::new(self::four::Control4::Two(self::four::two::Control4_2 {
-        field_1: name.len() as u16 * 2 + 2,
+        field_1: encode_text(header, name).len() as u16 + 2,
         string: name.clone(),
       })),
       Control::TextSize { percent } => Box::new(self::zero::Control0::Two(self::zero::two::Control0_2 {
@@ -297,14 +413,45 @@ impl Control {
         field_1: 2,
         field_2: font_kind.as_u16(),
       })),
+      Control::PageBreak => Box::new(self::zero::Control0::Four(self::zero::four::Control0_4 {
+        field_1: 0,
+      })),
+      Control::Ruby { base_chars, ref ruby } => Box::new(self::zero::Control0::Ruby(self::zero::ruby::Control0Ruby {
+        base_chars,
+        ruby: ruby.clone(),
+      })),
     };
 
     Ok(MainControlRef::Owned(b))
   }
 
   pub fn write(&self, header: &Header, mut writer: &mut dyn Write) -> Result<()> {
-    header.endianness().write_u16(&mut writer, 0x0e).with_context(|_| "could not write control marker")?;
-    let control = self.as_main_control()?;
+    write_code_unit(header, &mut writer, 0x0e).with_context(|_| "could not write control marker")?;
+    if let Control::Unknown { kind, sub_kind, ref data } = *self {
+      header.endianness().write_u16(&mut writer, kind)
+        .with_context(|_| format!("could not write control marker for type {}", kind))?;
+      header.endianness().write_u16(&mut writer, sub_kind)
+        .with_context(|_| format!("could not write subtype marker for type {}", kind))?;
+      header.endianness().write_u16(&mut writer, data.len() as u16)
+        .with_context(|_| format!("could not write parameter length for type {}", kind))?;
+      writer.write_all(data).with_context(|_| format!("could not write parameters for type {}", kind))?;
+      return Ok(());
+    }
+    if let Control::Tag { group, tag, ref name, ref params, ref padding } = *self {
+      let mut data = Vec::new();
+      crate::msbp::write_tag_values(header, params.values(), &mut data)
+        .with_context(|_| format!("could not write parameters for tag {}", name))?;
+      data.extend_from_slice(padding);
+      header.endianness().write_u16(&mut writer, group)
+        .with_context(|_| format!("could not write group for tag {}", name))?;
+      header.endianness().write_u16(&mut writer, tag)
+        .with_context(|_| format!("could not write tag {}", name))?;
+      header.endianness().write_u16(&mut writer, data.len() as u16)
+        .with_context(|_| format!("could not write parameter length for tag {}", name))?;
+      writer.write_all(&data).with_context(|_| format!("could not write parameters for tag {}", name))?;
+      return Ok(());
+    }
+    let control = self.as_main_control(header)?;
     header.endianness().write_u16(&mut writer, control.marker())
       .with_context(|_| format!("could not write control marker for type {}", control.marker()))?;
     control.write(header, &mut writer)
@@ -313,7 +460,7 @@ impl Control {
   }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum RawControl {
   Zero(self::zero::Control0),
@@ -339,7 +486,7 @@ impl RawControl {
   }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Colour {
   Red,
@@ -379,14 +526,14 @@ impl Colour {
   }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum PauseKind {
   Frames(u32),
   Length(PauseLength),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum PauseLength {
   Short,
@@ -414,7 +561,7 @@ impl PauseLength {
     }
   }
 }
-#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Icon {
   Zl(u8),
@@ -530,3 +677,127 @@ pub(crate) trait SubControl {
 
   fn write(&self, header: &Header, writer: &mut dyn Write) -> Result<()>;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use byteordered::Endianness;
+  use msbt::{Encoding, builder::MsbtBuilder};
+
+  #[test]
+  fn variable_length_counts_encoded_bytes() {
+    for &encoding in &[Encoding::Utf8, Encoding::Utf16] {
+      let builder = MsbtBuilder::new(Endianness::Little, encoding, Some(1));
+      let header = builder.header();
+      let control = Control::Variable {
+        variable_kind: VariableKind::String,
+        name: "Größe".to_string(),
+      };
+      let mut buf = Vec::new();
+      control.write(header, &mut buf).unwrap();
+
+      let unit = crate::game::code_unit_len(header);
+      let name_len = encode_text(header, "Größe").len();
+      // marker, type, subtype, then field_1
+      let field_1 = header.endianness().read_u16(&buf[unit + 4..]).unwrap();
+      assert_eq!(usize::from(field_1), name_len + 4);
+      assert_eq!(buf.len(), unit + 6 + field_1 as usize);
+
+      let parsed = Game::Botw.parse_controls(header, &buf).unwrap();
+      assert_eq!(parsed, vec![Content::Control(control)]);
+    }
+  }
+
+  #[test]
+  fn variables_with_a_format_stay_raw() {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf8, Some(1));
+    let control = Control::Raw(RawControl::Two(self::two::Control2::Variable(2, self::two::variable::Control2Variable {
+      field_1: encode_text(builder.header(), "Rubí").len() as u16 + 4,
+      string: "Rubí".to_string(),
+      field_3: 3,
+    })));
+    let mut buf = Vec::new();
+    control.write(builder.header(), &mut buf).unwrap();
+
+    let parsed = Game::Botw.parse_controls(builder.header(), &buf).unwrap();
+    assert_eq!(parsed, vec![Content::Control(control)]);
+  }
+
+  #[test]
+  fn variable_kinds_read_names_and_subtypes() {
+    let kind = |yaml: &str| {
+      let control: Control = serde_yaml::from_str(&format!("kind: unnamed_variable\nvariable_kind: {}", yaml)).unwrap();
+      match control {
+        Control::UnnamedVariable { variable_kind } => variable_kind,
+        c => panic!("expected an unnamed variable, got {:?}", c),
+      }
+    };
+    assert_eq!(kind("item_name"), VariableKind::ItemName);
+    assert_eq!(kind("9"), VariableKind::ItemName);
+    assert_eq!(kind("3"), VariableKind::PlayerName);
+    assert_eq!(kind("13"), VariableKind::Unknown(13));
+    assert_eq!(kind("{unknown: 13}"), VariableKind::Unknown(13));
+
+    assert!(serde_yaml::from_str::<VariableKind>("70000").is_err());
+    assert!(serde_yaml::from_str::<VariableKind>("horse_name").is_err());
+  }
+
+  #[test]
+  fn variable_kinds_write_what_they_read() {
+    for &kind in &[VariableKind::PlayerName, VariableKind::Unknown(13)] {
+      let yaml = serde_yaml::to_string(&kind).unwrap();
+      assert_eq!(serde_yaml::from_str::<VariableKind>(&yaml).unwrap(), kind);
+    }
+  }
+
+  #[test]
+  fn variable_kinds_must_match_their_layout() {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let write = |control: &Control| {
+      let mut buf = Vec::new();
+      control.write(builder.header(), &mut buf).map(|_| buf)
+    };
+
+    let matching = vec![
+      Control::Variable { variable_kind: VariableKind::ItemName, name: "Item".to_string() },
+      Control::UnnamedVariable { variable_kind: VariableKind::PlayerName },
+      Control::UnnamedVariable { variable_kind: VariableKind::Unknown(13) },
+    ];
+    for control in matching {
+      let buf = write(&control).unwrap();
+      let parsed = Game::Botw.parse_controls(builder.header(), &buf).unwrap();
+      assert_eq!(parsed, vec![Content::Control(control)]);
+    }
+
+    let mismatched = vec![
+      Control::Variable { variable_kind: VariableKind::PlayerName, name: "Link".to_string() },
+      Control::Variable { variable_kind: VariableKind::Unknown(20), name: "Link".to_string() },
+      Control::Variable { variable_kind: VariableKind::Unknown(4), name: "Count".to_string() },
+      Control::UnnamedVariable { variable_kind: VariableKind::ItemName },
+      Control::UnnamedVariable { variable_kind: VariableKind::Unknown(20) },
+    ];
+    for control in mismatched {
+      assert!(write(&control).is_err(), "{:?} should not be written", control);
+    }
+  }
+
+  #[test]
+  fn malformed_known_controls_are_kept_as_unknown() {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    // marker, type 1, subtype 8 and a parameter length too short for field_2
+    let buf = [0x0e, 0, 1, 0, 8, 0, 2, 0, 0xAA, 0xBB];
+
+    let parsed = Game::Botw.parse_controls(builder.header(), &buf).unwrap();
+    let control = Control::Unknown { kind: 1, sub_kind: 8, data: vec![0xAA, 0xBB] };
+    assert_eq!(parsed, vec![Content::Control(control)]);
+
+    let mut written = Vec::new();
+    for content in &parsed {
+      if let Content::Control(ref c) = *content {
+        c.write(builder.header(), &mut written).unwrap();
+      }
+    }
+    assert_eq!(written, buf);
+  }
+}
@@ -40,7 +40,7 @@ use self::{
   ten::Control1_10,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Control1 {
   Zero(Control1_0),
@@ -77,7 +77,7 @@ impl MainControl for Control1 {
       8 => Control1_8::parse(header, &mut c).with_context(|_| "could not parse control subtype 8")?,
       9 => Control1_9::parse(header, &mut c).with_context(|_| "could not parse control subtype 9")?,
       10 => Control1_10::parse(header, &mut c).with_context(|_| "could not parse control subtype 10")?,
-      x => failure::bail!("unknown control 1 type: {}", x),
+      _ => return Control::parse_unknown(header, 1, buf),
     };
 
     Ok((
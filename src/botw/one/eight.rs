@@ -16,7 +16,7 @@ use std::io::{Cursor, Read, Write};
 
 const UNKNOWN: [u8; 4] = [255, 255, 0, 0];
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control1_8 {
   unknown_1: Vec<[u8; 4]>,
   field_1: Vec<u16>,
@@ -30,7 +30,12 @@ impl SubControl for Control1_8 {
 
   fn parse(header: &Header, mut reader: &mut Cursor<&[u8]>) -> Result<Control> {
     let len = header.endianness().read_u16(&mut reader).with_context(|_| "could not read length")?;
-    let mut buf = vec![0; len as usize - 4];
+    // the last four bytes are field_2
+    let buf_len = match (len as usize).checked_sub(4) {
+      Some(l) => l,
+      None => failure::bail!("parameter length {} is too short for field_2", len),
+    };
+    let mut buf = vec![0; buf_len];
     reader.read_exact(&mut buf).with_context(|_| "could not read bytes")?;
 
     let mut unknown_count = 0;
@@ -14,7 +14,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control1_9 {
   unknown_1: Option<[u8; 12]>,
   strings: [Control1_9String; 4],
@@ -24,7 +24,7 @@ pub struct Control1_9 {
   field_6: [u8; 2],
 }
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default, PartialEq)]
 pub struct Control1_9String {
   field_1: u16,
   string: String,
@@ -98,7 +98,10 @@ impl SubControl for Control1_9 {
     let mut field_6 = [0; 2];
     reader.read_exact(&mut field_6).with_context(|_| "could not read field_6")?;
 
-    debug_assert_eq!(u64::from(payload_length), reader.position() - 4);
+    // the subtype and the length come before the parameters
+    if u64::from(payload_length) != reader.position() - 4 {
+      failure::bail!("parameter length {} does not match the {} bytes read", payload_length, reader.position() - 4);
+    }
 
     Ok(Control::Raw(RawControl::One(Control1::Nine(Control1_9 {
       unknown_1,
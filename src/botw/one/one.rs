@@ -14,7 +14,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control1_1 {
   field_1: u16,
   field_2: u32,
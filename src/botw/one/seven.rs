@@ -14,7 +14,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Read, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control1_7 {
   pub(crate) field_1: u16,
   pub(crate) field_2: [u8; 2],
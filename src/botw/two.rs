@@ -1,6 +1,7 @@
 use crate::{
   Result,
-  botw::{Control, MainControl, RawControl},
+  botw::{Control, MainControl, RawControl, VariableKind},
+  game::encode_text,
 };
 
 use byteordered::Endian;
@@ -21,7 +22,37 @@ use self::{
   variable::Control2Variable,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+/// The layout of a variable's parameters, which is decided by its subtype.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Layout {
+  /// A single field, which is zero for unnamed variables.
+  OneField,
+  /// A name followed by a format.
+  Variable,
+}
+
+impl Layout {
+  pub(crate) fn of(kind: u16) -> Option<Self> {
+    match kind {
+      3 | 4 | 7 | 8 | 10 | 13 => Some(Layout::OneField),
+      1 | 2 | 9 | 11 | 12 | 14 | 15 | 16 | 17 | 18 | 19 => Some(Layout::Variable),
+      _ => None,
+    }
+  }
+
+  /// The subtype of a variable of kind `kind`, if that kind uses this layout.
+  pub(crate) fn subtype(self, kind: VariableKind) -> Result<u16> {
+    let subtype = kind.as_u16();
+    match Layout::of(subtype) {
+      Some(layout) if layout == self => Ok(subtype),
+      Some(Layout::OneField) => failure::bail!("variables of kind {:?} (subtype {}) cannot have a name", kind, subtype),
+      Some(Layout::Variable) => failure::bail!("variables of kind {:?} (subtype {}) must have a name", kind, subtype),
+      None => failure::bail!("variables of kind {:?} (subtype {}) have no known layout", kind, subtype),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Control2 {
   OneField(u16, Control2OneField),
@@ -37,27 +68,36 @@ impl MainControl for Control2 {
     let mut c = Cursor::new(buf);
 
     let kind = header.endianness().read_u16(&mut c)?;
-    let control = match kind {
-      3 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 3")?),
-      4 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 4")?),
-      7 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 7")?),
-      8 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 8")?),
-      10 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 10")?),
-      13 => Control2::OneField(kind, Control2OneField::parse(header, &mut c).with_context(|_| "could not parse control subtype 13")?),
-      1 | 2 | 9 | 11 | 12 | 14 | 15 | 16 | 17 | 18 | 19 => {
-        let v = Control2Variable::parse(header, &mut c).with_context(|_| "could not parse control subtype 18")?;
-        if v.field_3 == 0 {
+    let control = match Layout::of(kind) {
+      Some(Layout::OneField) => {
+        let v = Control2OneField::parse(header, &mut c).with_context(|_| format!("could not parse control subtype {}", kind))?;
+        // field_1 is the length of the parameters, so these variables have none
+        if v.field_1 == 0 {
+          return Ok((
+            c.position() as usize,
+            Control::UnnamedVariable {
+              variable_kind: VariableKind::from_u16(kind),
+            },
+          ));
+        }
+        Control2::OneField(kind, v)
+      },
+      Some(Layout::Variable) => {
+        let v = Control2Variable::parse(header, &mut c).with_context(|_| format!("could not parse control subtype {}", kind))?;
+        // field_1 is the length of the parameters, so the variable is only decoded if writing it
+        // gives the same length. What a non-zero field_3 does is not known, so those stay raw.
+        if v.field_3 == 0 && usize::from(v.field_1) == encode_text(header, &v.string).len() + 4 {
           return Ok((
             c.position() as usize,
             Control::Variable {
-              variable_kind: kind,
+              variable_kind: VariableKind::from_u16(kind),
               name: v.string
             },
           ));
         }
         Control2::Variable(kind, v)
       },
-      x => failure::bail!("unknown control 2 type: {}", x),
+      None => return Control::parse_unknown(header, 2, buf),
     };
 
     Ok((
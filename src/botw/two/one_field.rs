@@ -10,20 +10,20 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control2OneField {
-  field_1: u16,
+  pub(crate) field_1: u16,
 }
 
 impl Control2OneField {
-  pub(crate) fn parse(header: &Header, mut reader: &mut Cursor<&[u8]>) -> Result<Self> {
+  pub(crate) fn parse(header: &Header, reader: &mut Cursor<&[u8]>) -> Result<Self> {
     Ok(Control2OneField {
-      field_1: header.endianness().read_u16(&mut reader).with_context(|_| "could not read field_1")?,
+      field_1: header.endianness().read_u16(reader).with_context(|_| "could not read field_1")?,
     })
   }
 
-  pub(crate) fn write(&self, header: &Header, mut writer: &mut dyn Write) -> Result<()> {
-    header.endianness().write_u16(&mut writer, self.field_1).with_context(|_| "could not write field_1")?;
+  pub(crate) fn write(&self, header: &Header, writer: &mut dyn Write) -> Result<()> {
+    header.endianness().write_u16(writer, self.field_1).with_context(|_| "could not write field_1")?;
 
     Ok(())
   }
@@ -23,7 +23,7 @@ use self::{
   localisation::Control201Localisation,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Control201 {
   Dynamic(u16, Control201Dynamic),
@@ -42,8 +42,8 @@ impl MainControl for Control201 {
     let kind = header.endianness().read_u16(&mut c)?;
     let control = match kind {
       0 => Control201::Dynamic(kind, Control201Dynamic::parse(header, &mut c).with_context(|_| "could not parse control subtype dynamic")?),
-      1 | 2 | 3 | 4 => Control201::OneField(kind, Control201OneField::parse(header, &mut c).with_context(|_| "could not parse control two fields")?),
-      5 | 6 | 7 | 8 => {
+      1..=4 => Control201::OneField(kind, Control201OneField::parse(header, &mut c).with_context(|_| "could not parse control two fields")?),
+      5..=8 => {
         let localisation_kind = Localisation::from_u16(kind);
         let sub = Control201Localisation::parse(header, &mut c).with_context(|_| "could not parse control subtype localisation")?;
         return Ok((
@@ -54,7 +54,7 @@ impl MainControl for Control201 {
           },
         ));
       },
-      x => failure::bail!("unknown control 201 type: {}", x),
+      _ => return Control::parse_unknown(header, 0xc9, buf),
     };
 
     Ok((
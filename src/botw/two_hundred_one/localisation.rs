@@ -10,7 +10,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Read, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control201Localisation {
   pub strings: Vec<String>,
 }
@@ -30,7 +30,7 @@ impl Control201Localisation {
           buf[0] as usize
         },
       };
-      total += str_len as usize + 2;
+      total += str_len + 2;
       if str_len == 0 {
         strings.push(Default::default());
         continue;
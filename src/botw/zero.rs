@@ -19,6 +19,7 @@ pub(crate) mod one;
 pub(crate) mod two;
 pub(crate) mod three;
 pub(crate) mod four;
+pub(crate) mod ruby;
 
 use self::{
   zero::Control0_0,
@@ -26,9 +27,10 @@ use self::{
   two::Control0_2,
   three::Control0_3,
   four::Control0_4,
+  ruby::Control0Ruby,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Control0 {
   Zero(Control0_0),
@@ -36,6 +38,7 @@ pub enum Control0 {
   Two(Control0_2),
   Three(Control0_3),
   Four(Control0_4),
+  Ruby(Control0Ruby),
 }
 
 impl MainControl for Control0 {
@@ -48,12 +51,12 @@ impl MainControl for Control0 {
 
     let kind = header.endianness().read_u16(&mut c)?;
     let control = match kind {
-      0 => Control0_0::parse(header, &mut c).with_context(|_| "could not parse control subtype 0")?,
+      0 => Control0Ruby::parse(header, &mut c).with_context(|_| "could not parse control subtype 0")?,
       1 => Control0_1::parse(header, &mut c).with_context(|_| "could not parse control subtype 1")?,
       2 => Control0_2::parse(header, &mut c).with_context(|_| "could not parse control subtype 2")?,
       3 => Control0_3::parse(header, &mut c).with_context(|_| "could not parse control subtype 3")?,
       4 => Control0_4::parse(header, &mut c).with_context(|_| "could not parse control subtype 4")?,
-      x => failure::bail!("unknown control 0 type: {}", x),
+      _ => return Control::parse_unknown(header, 0, buf),
     };
 
     Ok((
@@ -69,6 +72,7 @@ impl MainControl for Control0 {
       Control0::Two(ref c) => c as &dyn SubControl,
       Control0::Three(ref c) => c as &dyn SubControl,
       Control0::Four(ref c) => c as &dyn SubControl,
+      Control0::Ruby(ref c) => c as &dyn SubControl,
     };
 
     header.endianness().write_u16(&mut writer, sub.marker())
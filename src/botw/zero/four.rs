@@ -14,9 +14,9 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control0_4 {
-  field_1: u16,
+  pub(crate) field_1: u16,
 }
 
 impl SubControl for Control0_4 {
@@ -24,14 +24,20 @@ impl SubControl for Control0_4 {
     4
   }
 
-  fn parse(header: &Header, mut reader: &mut Cursor<&[u8]>) -> Result<Control> {
+  fn parse(header: &Header, reader: &mut Cursor<&[u8]>) -> Result<Control> {
+    let field_1 = header.endianness().read_u16(reader).with_context(|_| "could not read field_1")?;
+
+    if field_1 == 0 {
+      return Ok(Control::PageBreak);
+    }
+
     Ok(Control::Raw(RawControl::Zero(Control0::Four(Control0_4 {
-      field_1: header.endianness().read_u16(&mut reader).with_context(|_| "could not read field_1")?,
+      field_1,
     }))))
   }
 
-  fn write(&self, header: &Header, mut writer: &mut dyn Write) -> Result<()> {
-    header.endianness().write_u16(&mut writer, self.field_1).with_context(|_| "could not write field 1")?;
+  fn write(&self, header: &Header, writer: &mut dyn Write) -> Result<()> {
+    header.endianness().write_u16(writer, self.field_1).with_context(|_| "could not write field 1")?;
 
     Ok(())
   }
@@ -0,0 +1,135 @@
+use crate::{
+  Result,
+  botw::{Control, SubControl},
+  game::{code_unit_len, encode_text, parse_text},
+};
+use super::zero::Control0_0;
+
+use byteordered::Endian;
+
+use failure::ResultExt;
+
+use msbt::Header;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+  convert::TryFrom,
+  io::{Cursor, Read, Write},
+};
+
+/// Ruby (furigana) over the next `base_chars` code units of text.
+///
+/// The parameters are the length of the base text in bytes, the length of the ruby text in bytes
+/// and the ruby text itself.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Control0Ruby {
+  pub(crate) base_chars: u16,
+  pub(crate) ruby: String,
+}
+
+impl SubControl for Control0Ruby {
+  fn marker(&self) -> u16 {
+    0
+  }
+
+  fn parse(header: &Header, mut reader: &mut Cursor<&[u8]>) -> Result<Control> {
+    let start = reader.position();
+    let field_1 = header.endianness().read_u16(&mut reader).with_context(|_| "could not read field_1")?;
+    let field_2 = header.endianness().read_u16(&mut reader).with_context(|_| "could not read field_2")?;
+    let field_3 = header.endianness().read_u16(&mut reader).with_context(|_| "could not read field_3")?;
+
+    // field_1 is the length of the parameters, so anything else is not ruby that can be read. the
+    // base and ruby lengths must also be whole code units
+    let unit = code_unit_len(header) as u16;
+    if u32::from(field_1) != u32::from(field_3) + 4 || field_2 % unit != 0 || field_3 % unit != 0 {
+      reader.set_position(start);
+      return Control0_0::parse(header, reader);
+    }
+
+    let mut ruby = vec![0; field_3 as usize];
+    reader.read_exact(&mut ruby).with_context(|_| "could not read ruby bytes")?;
+
+    Ok(Control::Ruby {
+      base_chars: field_2 / unit,
+      ruby: parse_text(header, &ruby).with_context(|_| "could not parse ruby")?,
+    })
+  }
+
+  fn write(&self, header: &Header, mut writer: &mut dyn Write) -> Result<()> {
+    let ruby = encode_text(header, &self.ruby);
+    let ruby_len = match u16::try_from(ruby.len()) {
+      Ok(len) if len <= u16::MAX - 4 => len,
+      _ => failure::bail!("ruby {} is too long: {} bytes", self.ruby, ruby.len()),
+    };
+    let base_len = match self.base_chars.checked_mul(code_unit_len(header) as u16) {
+      Some(len) => len,
+      None => failure::bail!("ruby {} covers too many characters: {}", self.ruby, self.base_chars),
+    };
+    header.endianness().write_u16(&mut writer, ruby_len + 4).with_context(|_| "could not write parameter length")?;
+    header.endianness().write_u16(&mut writer, base_len).with_context(|_| "could not write base length")?;
+    header.endianness().write_u16(&mut writer, ruby_len).with_context(|_| "could not write ruby length")?;
+    writer.write_all(&ruby).with_context(|_| "could not write ruby")?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{botw::{RawControl, zero::Control0}, game::Game, model::Content};
+
+  use byteordered::Endianness;
+  use msbt::{Encoding, builder::MsbtBuilder};
+
+  #[test]
+  fn ruby_round_trips() {
+    for &encoding in &[Encoding::Utf8, Encoding::Utf16] {
+      let builder = MsbtBuilder::new(Endianness::Little, encoding, Some(1));
+      let header = builder.header();
+      let control = Control::Ruby {
+        base_chars: 2,
+        ruby: "かな".to_string(),
+      };
+      let mut buf = Vec::new();
+      control.write(header, &mut buf).unwrap();
+
+      let parsed = Game::Botw.parse_controls(header, &buf).unwrap();
+      assert_eq!(parsed, vec![Content::Control(control)]);
+    }
+  }
+
+  #[test]
+  fn partial_code_units_are_kept_raw() {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let header = builder.header();
+    // a base length of three bytes, which is not a whole number of utf-16 code units
+    let buf = [0x0e, 0, 0, 0, 0, 0, 6, 0, 3, 0, 2, 0, 0x4B, 0x30];
+
+    let parsed = Game::Botw.parse_controls(header, &buf).unwrap();
+    let raw = Control::Raw(RawControl::Zero(Control0::Zero(Control0_0 {
+      field_1: 6,
+      field_2: 3,
+      field_3: 2,
+    })));
+    assert_eq!(parsed, vec![Content::Control(raw), Content::Text("か".to_string())]);
+  }
+
+  #[test]
+  fn oversized_ruby_is_rejected() {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let long = Control::Ruby {
+      base_chars: 1,
+      ruby: "か".repeat(0x8000),
+    };
+    assert!(long.write(builder.header(), &mut Vec::new()).is_err());
+
+    let wide = Control::Ruby {
+      base_chars: 0x8000,
+      ruby: "か".to_string(),
+    };
+    assert!(wide.write(builder.header(), &mut Vec::new()).is_err());
+  }
+}
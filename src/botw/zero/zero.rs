@@ -14,11 +14,11 @@ use serde_derive::{Deserialize, Serialize};
 
 use std::io::{Cursor, Write};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Control0_0 {
-  field_1: u16,
-  field_2: u16,
-  field_3: u16,
+  pub(crate) field_1: u16,
+  pub(crate) field_2: u16,
+  pub(crate) field_3: u16,
 }
 
 impl SubControl for Control0_0 {
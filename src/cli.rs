@@ -1,5 +1,7 @@
 use clap::{App, AppSettings, Arg, SubCommand};
 
+use msyt::game::Game;
+
 pub fn app<'a, 'b: 'a>() -> App<'a, 'b> {
   App::new(clap::crate_name!())
     .version(clap::crate_version!())
@@ -41,6 +43,21 @@ pub fn app<'a, 'b: 'a>() -> App<'a, 'b> {
         .long("output")
         .takes_value(true))
 
+      .arg(Arg::with_name("prune")
+        .help("Remove labels from the MSBT files that are not in the MSYT files")
+        .long("prune"))
+
+      .arg(Arg::with_name("archive")
+        .help("A SARC archive containing the MSBT files to import into. The archive is repacked with the imported files.")
+        .short("a")
+        .long("archive")
+        .takes_value(true))
+
+      .arg(Arg::with_name("zstd_dict")
+        .help("The zstd dictionary that archives are compressed with, either as a raw dictionary or the ZsDic.pack.zs it comes in")
+        .long("zstd-dict")
+        .takes_value(true))
+
       .arg(Arg::with_name("paths")
         .help("MSYT paths to import (MSBT files should be adjacent)")
         .required(true)
@@ -69,50 +86,261 @@ pub fn app<'a, 'b: 'a>() -> App<'a, 'b> {
         .default_value("msbt"))
 
       .arg(Arg::with_name("platform")
-        .help("The platform to create the MSBT for")
+        .help("The platform to create the MSBT for, overriding the platform in the MSYT")
         .short("p")
         .long("platform")
         .takes_value(true)
-        .required(true)
         .possible_values(&["switch", "wiiu"]))
 
       .arg(Arg::with_name("encoding")
-        .help("The encoding to create the MSBT with")
+        .help("The encoding to create the MSBT with, overriding the encoding in the MSYT (utf16 if neither is given)")
         .short("E")
         .long("encoding")
         .takes_value(true)
-        .required(true)
-        .possible_values(&["utf16", "utf8"])
-        .default_value("utf16"))
+        .possible_values(&["utf16", "utf8"]))
 
       .arg(Arg::with_name("output")
         .help("The directory to place output files in")
         .short("o")
         .long("output")
         .takes_value(true)
-        .required(true))
+        .required_unless("archive"))
+
+      .arg(Arg::with_name("archive")
+        .help("A SARC archive to pack the created MSBT files into instead of writing them to a directory. Existing archives are updated.")
+        .short("a")
+        .long("archive")
+        .takes_value(true))
+
+      .arg(Arg::with_name("zstd_dict")
+        .help("The zstd dictionary that archives are compressed with, either as a raw dictionary or the ZsDic.pack.zs it comes in")
+        .long("zstd-dict")
+        .takes_value(true))
 
       .arg(Arg::with_name("paths")
         .help("MSYT paths to create MSBT files from")
         .required(true)
         .multiple(true)))
+    .subcommand(SubCommand::with_name("convert")
+      .about("Convert MSBT files to a different platform or encoding")
+
+      .arg(Arg::with_name("game")
+        .help("The game the MSBT files are from, which decides how control sequences are decoded. Use generic for other games to keep every control sequence as raw data.")
+        .short("g")
+        .long("game")
+        .takes_value(true)
+        .possible_values(Game::NAMES)
+        .default_value("botw"))
+
+      .arg(Arg::with_name("dir_mode")
+        .help("Allow specifying directories. msyt will search for all files with the correct extension in the provided directories.")
+        .short("d")
+        .long("directories")
+        .alias("directory"))
+
+      .arg(Arg::with_name("no-backup")
+        .help("Do not create a backup of any existing output files")
+        .short("B")
+        .long("no-backup"))
+
+      .arg(Arg::with_name("platform")
+        .help("The platform to convert the MSBT files to")
+        .short("p")
+        .long("platform")
+        .takes_value(true)
+        .possible_values(&["switch", "wiiu"])
+        .required_unless_one(&["encoding", "icon"]))
+
+      .arg(Arg::with_name("encoding")
+        .help("The encoding to convert the MSBT files to")
+        .short("E")
+        .long("encoding")
+        .takes_value(true)
+        .possible_values(&["utf16", "utf8"]))
+
+      .arg(Arg::with_name("icon")
+        .help("Replace an icon value with another, given as from:to. May be given more than once.")
+        .short("i")
+        .long("icon")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1))
+
+      .arg(Arg::with_name("keep_opaque")
+        .help("Copy parts that msyt cannot convert, such as unknown control sequences and sections, unchanged instead of failing")
+        .long("keep-opaque"))
+
+      .arg(Arg::with_name("output")
+        .help("The directory to place output files in. If not specified, input files are converted in place.")
+        .short("o")
+        .long("output")
+        .takes_value(true))
+
+      .arg(Arg::with_name("zstd_dict")
+        .help("The zstd dictionary that archives are compressed with, either as a raw dictionary or the ZsDic.pack.zs it comes in")
+        .long("zstd-dict")
+        .takes_value(true))
+
+      .arg(Arg::with_name("paths")
+        .help("MSBT paths to convert. SARC archives (.sarc, .ssarc) are converted along with every MSBT inside.")
+        .required(true)
+        .multiple(true)))
     .subcommand(SubCommand::with_name("export")
       .about("Export from MSBT files to MSYT files")
 
+      .arg(Arg::with_name("game")
+        .help("The game the MSBT files are from, which decides how control sequences are decoded. Use generic for other games to keep every control sequence as raw data.")
+        .short("g")
+        .long("game")
+        .takes_value(true)
+        .possible_values(Game::NAMES)
+        .default_value("botw"))
+
+      .arg(Arg::with_name("msbp")
+        .help("An MSBP project file describing the tags and attributes of the MSBT files. Control sequences and attributes are decoded with it instead of --game.")
+        .long("msbp")
+        .takes_value(true))
+
       .arg(Arg::with_name("dir_mode")
         .help("Allow specifying directories. msyt will search for all files with the correct extension in the provided directories.")
         .short("d")
         .long("directories")
         .alias("directory"))
 
+      .arg(Arg::with_name("sort")
+        .help("Sort entries by label instead of keeping the order of the MSBT. The original order is recorded so create can restore it.")
+        .short("s")
+        .long("sort"))
+
+      .arg(Arg::with_name("pages")
+        .help("Split the contents of each entry into pages, one for each text box, at page breaks. Pages are joined with page breaks again when creating or importing.")
+        .long("pages"))
+
       .arg(Arg::with_name("output")
         .help("The directory to place output files in. If not specified, output files will be placed next to input files.")
         .short("o")
         .long("output")
         .takes_value(true))
 
+      .arg(Arg::with_name("zstd_dict")
+        .help("The zstd dictionary that archives are compressed with, either as a raw dictionary or the ZsDic.pack.zs it comes in")
+        .long("zstd-dict")
+        .takes_value(true))
+
+      .arg(Arg::with_name("paths")
+        .help("MSBT paths to export. SARC archives (.sarc, .ssarc) are unpacked and every MSBT inside is exported.")
+        .required(true)
+        .multiple(true)))
+    .subcommand(SubCommand::with_name("diff")
+      .about("Compare two MSBT or MSYT files label by label")
+
+      .arg(Arg::with_name("game")
+        .help("The game the MSBT files are from, which decides how control sequences are decoded. Use generic for other games to keep every control sequence as raw data.")
+        .short("g")
+        .long("game")
+        .takes_value(true)
+        .possible_values(Game::NAMES)
+        .default_value("botw"))
+
+      .arg(Arg::with_name("json")
+        .help("Output the differences as JSON")
+        .short("j")
+        .long("json"))
+
+      .arg(Arg::with_name("old")
+        .help("The original MSBT or MSYT file")
+        .required(true))
+
+      .arg(Arg::with_name("new")
+        .help("The MSBT or MSYT file to compare against the original")
+        .required(true)))
+    .subcommand(SubCommand::with_name("verify")
+      .about("Check that MSBT files survive a round-trip through MSYT unchanged")
+
+      .arg(Arg::with_name("game")
+        .help("The game the MSBT files are from, which decides how control sequences are decoded. Use generic for other games to keep every control sequence as raw data.")
+        .short("g")
+        .long("game")
+        .takes_value(true)
+        .possible_values(Game::NAMES)
+        .default_value("botw"))
+
+      .arg(Arg::with_name("dir_mode")
+        .help("Allow specifying directories. msyt will search for all files with the correct extension in the provided directories.")
+        .short("d")
+        .long("directories")
+        .alias("directory"))
+
+      .arg(Arg::with_name("compare")
+        .help("Instead of round-tripping, compare each MSBT to the file at the same relative path in this directory")
+        .short("c")
+        .long("compare")
+        .takes_value(true))
+
       .arg(Arg::with_name("paths")
-        .help("MSBT paths to export")
+        .help("MSBT paths to verify")
         .required(true)
         .multiple(true)))
+    .subcommand(SubCommand::with_name("msbf")
+      .about("Export and create MSBF flowcharts")
+
+      .setting(AppSettings::SubcommandRequiredElseHelp)
+
+      .subcommand(SubCommand::with_name("export")
+        .about("Export from MSBF files to YAML files. The MSBT or MSYT with the same name, if any, is used to name messages and choices.")
+
+        .arg(Arg::with_name("game")
+          .help("The game the paired MSBT files are from, which decides how control sequences are decoded.")
+          .short("g")
+          .long("game")
+          .takes_value(true)
+          .possible_values(Game::NAMES)
+          .default_value("botw"))
+
+        .arg(Arg::with_name("msbp")
+          .help("An MSBP project file describing the tags of the paired MSBT files. Used instead of --game.")
+          .long("msbp")
+          .takes_value(true))
+
+        .arg(Arg::with_name("dir_mode")
+          .help("Allow specifying directories. msyt will search for all files with the correct extension in the provided directories.")
+          .short("d")
+          .long("directories")
+          .alias("directory"))
+
+        .arg(Arg::with_name("output")
+          .help("The output directory to place exported files in. If not specified, exported files will be written next to the files they are exported from.")
+          .short("o")
+          .long("output")
+          .takes_value(true))
+
+        .arg(Arg::with_name("paths")
+          .help("MSBF paths to export")
+          .required(true)
+          .multiple(true)))
+
+      .subcommand(SubCommand::with_name("create")
+        .about("Create MSBF files from YAML files exported with msbf export")
+
+        .arg(Arg::with_name("dir_mode")
+          .help("Allow specifying directories. msyt will search for all .msbf.yml files in the provided directories.")
+          .short("d")
+          .long("directories")
+          .alias("directory"))
+
+        .arg(Arg::with_name("no-backup")
+          .help("Do not create a backup of any existing output files")
+          .short("B")
+          .long("no-backup"))
+
+        .arg(Arg::with_name("output")
+          .help("The output directory to place created files in. If not specified, created files will be written next to the files they are created from.")
+          .short("o")
+          .long("output")
+          .takes_value(true))
+
+        .arg(Arg::with_name("paths")
+          .help("Paths of .msbf.yml files to create MSBF files from")
+          .required(true)
+          .multiple(true))))
 }
@@ -0,0 +1,296 @@
+//! Retargeting MSBTs to a different byte order or text encoding.
+
+use crate::{
+  Msyt,
+  Result,
+  botw::{
+    Control,
+    Icon,
+    RawControl,
+    zero::Control0,
+    one::Control1,
+    two::Control2,
+    four::Control4,
+    two_hundred_one::Control201,
+  },
+  game::Game,
+  model::{Content, Platform, TextEncoding},
+};
+
+use byteordered::Endianness;
+use msbt::Encoding;
+
+use std::collections::HashMap;
+
+/// What to convert an MSBT to.
+#[derive(Debug, Default)]
+pub struct Conversion {
+  /// The game whose controls are decoded before writing them again.
+  pub game: Game,
+  /// The byte order to convert to, or `None` to keep the original.
+  pub endianness: Option<Endianness>,
+  /// The text encoding to convert to, or `None` to keep the original.
+  pub encoding: Option<Encoding>,
+  /// Icon values to replace, as the original value mapped to its replacement.
+  pub icons: HashMap<u8, u8>,
+  /// Copy parts that msyt cannot convert unchanged instead of failing. See [`Conversion::convert`].
+  pub keep_opaque: bool,
+}
+
+impl Conversion {
+  /// Convert the MSBT contained in `bytes`, keeping its section order.
+  ///
+  /// Every label and control sequence is decoded and written again with the new header. TSY1 is
+  /// byte-swapped if the byte order changes.
+  ///
+  /// Some parts are opaque to msyt and would be copied unchanged: ATO1, unknown sections and
+  /// controls holding bytes of unknown meaning, such as sounds and the payloads of unknown
+  /// control sequences, if the byte order changes, and controls that store the length of a string
+  /// in bytes if the encoding changes. If any of these are present, conversion fails and lists
+  /// them, unless `keep_opaque` is set.
+  ///
+  /// Returns the converted MSBT and the parts that were copied unchanged.
+  pub fn convert(&self, bytes: &[u8]) -> Result<(Vec<u8>, Vec<String>)> {
+    let mut msyt = Msyt::from_msbt_bytes(bytes, &self.game)?;
+
+    let old_endianness = msyt.msbt.platform
+      .expect("platform recorded when exporting")
+      .endianness();
+    let endianness = self.endianness.unwrap_or(old_endianness);
+    let encoding = match self.encoding {
+      Some(e) => e,
+      None => msyt.msbt.encoding.map(Into::into).unwrap_or(Encoding::Utf16),
+    };
+
+    let old_encoding = msyt.msbt.encoding.map(Into::into).unwrap_or(Encoding::Utf16);
+    let opaque = opaque_parts(&msyt, endianness != old_endianness, encoding != old_encoding);
+    if !opaque.is_empty() && !self.keep_opaque {
+      failure::bail!(
+        "these parts cannot be converted and would be copied unchanged (use --keep-opaque to allow this):\n  {}",
+        opaque.join("\n  "),
+      );
+    }
+
+    if endianness != old_endianness {
+      if let Some(ref mut tsy1) = msyt.msbt.tsy1 {
+        // TSY1 holds a u32 style index for each string
+        if tsy1.len() % 4 == 0 {
+          for index in tsy1.chunks_mut(4) {
+            index.reverse();
+          }
+        }
+      }
+    }
+    msyt.msbt.platform = Some(Platform::from_endianness(endianness));
+    msyt.msbt.encoding = Some(TextEncoding::from(encoding));
+
+    if !self.icons.is_empty() {
+      let controls = msyt.entries
+        .values_mut()
+        .flat_map(|e| e.contents.iter_mut())
+        .filter_map(|c| match *c {
+          Content::Control(ref mut c) => Some(c),
+          _ => None,
+        });
+      for control in controls {
+        if let Control::Icon { ref mut icon } = *control {
+          if let Some(&new) = self.icons.get(&icon.as_u8()) {
+            *icon = Icon::from_u8(new);
+          }
+        }
+      }
+    }
+
+    // the header and section order recorded when exporting are kept
+    let converted = msyt.into_msbt_bytes(endianness, encoding)?;
+    Ok((converted, opaque))
+  }
+}
+
+/// The parts of `msyt` that would be copied unchanged although the byte order or encoding changes.
+fn opaque_parts(msyt: &Msyt, endianness_changed: bool, encoding_changed: bool) -> Vec<String> {
+  let mut parts = Vec::new();
+  if !endianness_changed && !encoding_changed {
+    return parts;
+  }
+
+  if endianness_changed {
+    if msyt.msbt.ato1.as_ref().map(|a| !a.is_empty()).unwrap_or(false) {
+      parts.push("section ato1".to_string());
+    }
+    for (magic, data) in &msyt.msbt.unknown_sections {
+      if !data.0.is_empty() {
+        parts.push(format!("section {}", magic.to_lowercase()));
+      }
+    }
+  }
+
+  for (label, entry) in &msyt.entries {
+    for content in &entry.contents {
+      let control = match *content {
+        Content::Control(ref c) => c,
+        _ => continue,
+      };
+      let opaque = Opaque::of(control);
+      if (endianness_changed && opaque.bytes) || (encoding_changed && opaque.lengths) {
+        let part = match *control {
+          Control::Unknown { kind, sub_kind, .. } => format!("control type {} subtype {}", kind, sub_kind),
+          Control::Raw(_) => format!("raw control {:?}", control),
+          _ => format!("control {:?}", control),
+        };
+        parts.push(format!("label {}: {}", label, part));
+      }
+    }
+  }
+
+  parts
+}
+
+/// What a control holds that is copied unchanged when it is written again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Opaque {
+  /// Bytes whose meaning is not known, so they are not byte-swapped.
+  bytes: bool,
+  /// The length of a string or of the parameters in bytes, which is not recomputed.
+  lengths: bool,
+}
+
+impl Opaque {
+  const NONE: Opaque = Opaque { bytes: false, lengths: false };
+  const BYTES: Opaque = Opaque { bytes: true, lengths: false };
+  const LENGTHS: Opaque = Opaque { bytes: false, lengths: true };
+  const BOTH: Opaque = Opaque { bytes: true, lengths: true };
+
+  fn bytes_if(present: bool) -> Self {
+    if present { Opaque::BYTES } else { Opaque::NONE }
+  }
+
+  // every variant is listed, so that new ones have to be considered here
+  fn of(control: &Control) -> Self {
+    match *control {
+      Control::Unknown { ref data, .. } => if data.is_empty() { Opaque::NONE } else { Opaque::BOTH },
+      // padding aligns the parameters, which may change size with the encoding
+      Control::Tag { ref padding, .. } => if padding.is_empty() { Opaque::NONE } else { Opaque::BOTH },
+      Control::Sound { ref unknown } | Control::Sound2 { ref unknown } => Opaque::bytes_if(!unknown.is_empty()),
+      Control::Raw(ref raw) => Opaque::of_raw(raw),
+      Control::SetColour { .. }
+        | Control::ResetColour
+        | Control::Pause(_)
+        | Control::Icon { .. }
+        | Control::Variable { .. }
+        | Control::UnnamedVariable { .. }
+        | Control::Choice { .. }
+        | Control::SingleChoice { .. }
+        | Control::Animation { .. }
+        | Control::TextSize { .. }
+        | Control::AutoAdvance { .. }
+        | Control::Localisation { .. }
+        | Control::Font { .. }
+        | Control::PageBreak
+        | Control::Ruby { .. } => Opaque::NONE,
+    }
+  }
+
+  fn of_raw(raw: &RawControl) -> Self {
+    match *raw {
+      RawControl::Zero(ref c) => match *c {
+        Control0::Zero(_) => Opaque::LENGTHS,
+        Control0::One(_) | Control0::Two(_) | Control0::Three(_) | Control0::Four(_) | Control0::Ruby(_) => Opaque::NONE,
+      },
+      RawControl::One(ref c) => match *c {
+        Control1::Eight(_) | Control1::Nine(_) => Opaque::BYTES,
+        Control1::Zero(_)
+          | Control1::One(_)
+          | Control1::Two(_)
+          | Control1::Three(_)
+          | Control1::Four(_)
+          | Control1::Five(_)
+          | Control1::Six(_)
+          | Control1::Seven(_)
+          | Control1::Ten(_) => Opaque::NONE,
+      },
+      RawControl::Two(ref c) => match *c {
+        Control2::Variable(..) => Opaque::LENGTHS,
+        Control2::OneField(..) => Opaque::NONE,
+      },
+      RawControl::Three(ref c) => Opaque::bytes_if(!c.field_2.is_empty()),
+      RawControl::Four(ref c) => match *c {
+        Control4::Zero(_) | Control4::Two(_) => Opaque::LENGTHS,
+        Control4::One(ref c) => Opaque::bytes_if(!c.field_1.is_empty()),
+        Control4::Three(_) => Opaque::NONE,
+      },
+      RawControl::Five(_) => Opaque::NONE,
+      RawControl::TwoHundredOne(ref c) => match *c {
+        Control201::Dynamic(_, ref c) => if c.field_2.is_empty() { Opaque::NONE } else { Opaque::BOTH },
+        Control201::OneField(..) | Control201::Localisation(..) => Opaque::NONE,
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn msbt_with(control: &str) -> Vec<u8> {
+    let yaml = format!(
+      "group_count: 1\nentries:\n  a:\n    contents:\n      - text: hi\n      - control:\n{}",
+      control,
+    );
+    let msyt: Msyt = serde_yaml::from_str(&yaml).unwrap();
+    msyt.into_msbt_bytes(Endianness::Little, Encoding::Utf16).unwrap()
+  }
+
+  #[test]
+  fn unknown_payload_blocks_platform_change() {
+    let msbt = msbt_with("          kind: unknown\n          control_kind: 7\n          sub_kind: 1\n          data: AAEAAg==\n");
+    let conversion = Conversion {
+      game: Game::Generic,
+      endianness: Some(Endianness::Big),
+      ..Default::default()
+    };
+    assert!(conversion.convert(&msbt).is_err());
+
+    let conversion = Conversion {
+      keep_opaque: true,
+      ..conversion
+    };
+    let (_, opaque) = conversion.convert(&msbt).unwrap();
+    assert_eq!(opaque, vec!["label a: control type 7 subtype 1".to_string()]);
+  }
+
+  #[test]
+  fn sound_payload_blocks_platform_change_only() {
+    let msbt = msbt_with("          kind: sound\n          unknown: [1, 2]\n");
+    let conversion = Conversion {
+      endianness: Some(Endianness::Big),
+      ..Default::default()
+    };
+    let err = conversion.convert(&msbt).unwrap_err();
+    assert!(err.to_string().contains("label a: control Sound"));
+
+    let conversion = Conversion {
+      encoding: Some(Encoding::Utf8),
+      ..Default::default()
+    };
+    let (_, opaque) = conversion.convert(&msbt).unwrap();
+    assert!(opaque.is_empty());
+  }
+
+  #[test]
+  fn known_controls_convert_cleanly() {
+    let msbt = msbt_with("          kind: text_size\n          percent: 150\n");
+    let conversion = Conversion {
+      endianness: Some(Endianness::Big),
+      encoding: Some(Encoding::Utf8),
+      ..Default::default()
+    };
+    let (converted, opaque) = conversion.convert(&msbt).unwrap();
+    assert!(opaque.is_empty());
+
+    let original = Msyt::from_msbt_bytes(&msbt, &Game::Botw).unwrap();
+    let converted = Msyt::from_msbt_bytes(&converted, &Game::Botw).unwrap();
+    assert_eq!(converted.msbt.platform, Some(Platform::WiiU));
+    assert_eq!(converted.entries, original.entries);
+  }
+}
@@ -0,0 +1,406 @@
+use crate::{
+  botw::Control,
+  model::{Content, Entry, HeaderInfo, Msyt, Nli1, Platform, TextEncoding},
+  msbp::Attribute,
+};
+
+use indexmap::IndexMap;
+use serde_derive::Serialize;
+
+use std::fmt::{self, Display, Formatter};
+
+/// The differences between two MSYTs.
+#[derive(Debug, Serialize)]
+pub struct MsytDiff<'a> {
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub sections: Vec<SectionChange>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub added: Vec<&'a str>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub removed: Vec<&'a str>,
+  #[serde(skip_serializing_if = "IndexMap::is_empty")]
+  pub changed: IndexMap<&'a str, EntryDiff<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change<T> {
+  pub old: T,
+  pub new: T,
+}
+
+impl<T: PartialEq> Change<T> {
+  fn of(old: T, new: T) -> Option<Self> {
+    if old == new {
+      None
+    } else {
+      Some(Change { old, new })
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "section")]
+pub enum SectionChange {
+  Platform(Change<Option<Platform>>),
+  Encoding(Change<Option<TextEncoding>>),
+  Header(Change<Option<HeaderInfo>>),
+  GroupCount(Change<u32>),
+  Atr1Unknown(Change<Option<u32>>),
+  /// ATO1 is stored as opaque bytes, so only its length is reported.
+  Ato1(Change<Option<usize>>),
+  /// TSY1 is stored as opaque bytes, so only its length is reported.
+  Tsy1(Change<Option<usize>>),
+  Nli1(Nli1Diff),
+  /// Unknown sections are stored as opaque bytes, so only their lengths are reported.
+  Unknown { magic: String, change: Change<Option<usize>> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct Nli1Diff {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id_count: Option<Change<Option<u32>>>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub added: Vec<u32>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub removed: Vec<u32>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub changed: Vec<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryDiff<'a> {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub attributes: Option<Change<Option<&'a str>>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub attribute_fields: Option<Change<Option<&'a IndexMap<String, Attribute>>>>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub contents: Vec<ContentChange<'a>>,
+}
+
+/// A change to the contents of an entry. Pages are compared as their contents separated by page
+/// breaks, which is how they are written, so indices count the contents of every page.
+///
+/// The old and new contents are aligned on their longest common subsequence first, so inserting or
+/// removing one item does not mark every item after it as changed. A removal and an addition at
+/// the same place are reported as a change.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "change")]
+pub enum ContentChange<'a> {
+  /// `index` is the position in the new contents.
+  Added { index: usize, content: &'a Content },
+  /// `index` is the position in the old contents.
+  Removed { index: usize, content: &'a Content },
+  Changed { old_index: usize, new_index: usize, old: &'a Content, new: &'a Content },
+}
+
+impl<'a> MsytDiff<'a> {
+  pub fn new(old: &'a Msyt, new: &'a Msyt) -> Self {
+    let mut sections = Vec::new();
+    sections.extend(Change::of(old.msbt.platform, new.msbt.platform).map(SectionChange::Platform));
+    sections.extend(Change::of(old.msbt.encoding, new.msbt.encoding).map(SectionChange::Encoding));
+    sections.extend(Change::of(old.msbt.header.clone(), new.msbt.header.clone()).map(SectionChange::Header));
+    sections.extend(Change::of(old.msbt.group_count, new.msbt.group_count).map(SectionChange::GroupCount));
+    sections.extend(Change::of(old.msbt.atr1_unknown, new.msbt.atr1_unknown).map(SectionChange::Atr1Unknown));
+    if old.msbt.ato1 != new.msbt.ato1 {
+      sections.push(SectionChange::Ato1(Change {
+        old: old.msbt.ato1.as_ref().map(Vec::len),
+        new: new.msbt.ato1.as_ref().map(Vec::len),
+      }));
+    }
+    if old.msbt.tsy1 != new.msbt.tsy1 {
+      sections.push(SectionChange::Tsy1(Change {
+        old: old.msbt.tsy1.as_ref().map(Vec::len),
+        new: new.msbt.tsy1.as_ref().map(Vec::len),
+      }));
+    }
+    if old.msbt.nli1 != new.msbt.nli1 {
+      sections.push(SectionChange::Nli1(Nli1Diff::new(old.msbt.nli1.as_ref(), new.msbt.nli1.as_ref())));
+    }
+    let magics = old.msbt.unknown_sections.keys()
+      .chain(new.msbt.unknown_sections.keys().filter(|k| !old.msbt.unknown_sections.contains_key(*k)));
+    for magic in magics {
+      let (old_section, new_section) = (old.msbt.unknown_sections.get(magic), new.msbt.unknown_sections.get(magic));
+      if old_section != new_section {
+        sections.push(SectionChange::Unknown {
+          magic: magic.clone(),
+          change: Change {
+            old: old_section.map(|s| s.0.len()),
+            new: new_section.map(|s| s.0.len()),
+          },
+        });
+      }
+    }
+
+    let added = new.entries.keys()
+      .filter(|k| !old.entries.contains_key(*k))
+      .map(String::as_str)
+      .collect();
+    let removed = old.entries.keys()
+      .filter(|k| !new.entries.contains_key(*k))
+      .map(String::as_str)
+      .collect();
+    let changed = old.entries.iter()
+      .filter_map(|(k, old_entry)| new.entries.get(k).map(|new_entry| (k.as_str(), old_entry, new_entry)))
+      .filter_map(|(k, old_entry, new_entry)| EntryDiff::new(old_entry, new_entry).map(|d| (k, d)))
+      .collect();
+
+    MsytDiff {
+      sections,
+      added,
+      removed,
+      changed,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.sections.is_empty() && self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+  }
+}
+
+impl Nli1Diff {
+  fn new(old: Option<&Nli1>, new: Option<&Nli1>) -> Self {
+    let empty = Default::default();
+    let old_ids = old.map(|n| &n.global_ids).unwrap_or(&empty);
+    let new_ids = new.map(|n| &n.global_ids).unwrap_or(&empty);
+
+    Nli1Diff {
+      id_count: Change::of(old.map(|n| n.id_count), new.map(|n| n.id_count)),
+      added: new_ids.keys().filter(|k| !old_ids.contains_key(*k)).cloned().collect(),
+      removed: old_ids.keys().filter(|k| !new_ids.contains_key(*k)).cloned().collect(),
+      changed: old_ids.iter()
+        .filter(|(k, v)| new_ids.get(*k).map(|nv| nv != *v).unwrap_or(false))
+        .map(|(k, _)| *k)
+        .collect(),
+    }
+  }
+}
+
+impl<'a> EntryDiff<'a> {
+  fn new(old: &'a Entry, new: &'a Entry) -> Option<Self> {
+    let attributes = Change::of(old.attributes.as_deref(), new.attributes.as_deref());
+    let attribute_fields = Change::of(old.attribute_fields.as_ref(), new.attribute_fields.as_ref());
+
+    let contents = ContentChange::between(&flatten(&old.contents), &flatten(&new.contents));
+
+    if attributes.is_none() && attribute_fields.is_none() && contents.is_empty() {
+      return None;
+    }
+
+    Some(EntryDiff {
+      attributes,
+      attribute_fields,
+      contents,
+    })
+  }
+}
+
+impl<'a> ContentChange<'a> {
+  /// The changes that turn `old` into `new`.
+  fn between(old: &[&'a Content], new: &[&'a Content]) -> Vec<Self> {
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+      for j in (0..new.len()).rev() {
+        lengths[i][j] = if old[i] == new[j] {
+          lengths[i + 1][j + 1] + 1
+        } else {
+          std::cmp::max(lengths[i + 1][j], lengths[i][j + 1])
+        };
+      }
+    }
+
+    let mut changes = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+      if i < old.len() && j < new.len() && old[i] == new[j] {
+        ContentChange::flush(&mut changes, &mut removed, &mut added, old, new);
+        i += 1;
+        j += 1;
+      } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+        added.push(j);
+        j += 1;
+      } else {
+        removed.push(i);
+        i += 1;
+      }
+    }
+    ContentChange::flush(&mut changes, &mut removed, &mut added, old, new);
+
+    changes
+  }
+
+  /// Turn the items removed and added between two matching items into changes, pairing them up in
+  /// order.
+  fn flush(changes: &mut Vec<Self>, removed: &mut Vec<usize>, added: &mut Vec<usize>, old: &[&'a Content], new: &[&'a Content]) {
+    let paired = std::cmp::min(removed.len(), added.len());
+    for (&old_index, &new_index) in removed.iter().zip(added.iter()) {
+      changes.push(ContentChange::Changed { old_index, new_index, old: old[old_index], new: new[new_index] });
+    }
+    for &index in &removed[paired..] {
+      changes.push(ContentChange::Removed { index, content: old[index] });
+    }
+    for &index in &added[paired..] {
+      changes.push(ContentChange::Added { index, content: new[index] });
+    }
+    removed.clear();
+    added.clear();
+  }
+}
+
+/// The page break written between consecutive pages.
+static PAGE_BREAK: Content = Content::Control(Control::PageBreak);
+
+/// The contents of an entry as they are written, with pages replaced by their contents and
+/// consecutive pages separated by page breaks.
+fn flatten(contents: &[Content]) -> Vec<&Content> {
+  let mut flat = Vec::with_capacity(contents.len());
+  let mut previous_page = false;
+  for content in contents {
+    match *content {
+      Content::Page(ref page) => {
+        if previous_page {
+          flat.push(&PAGE_BREAK);
+        }
+        flat.extend(flatten(page));
+        previous_page = true;
+      },
+      ref c => {
+        flat.push(c);
+        previous_page = false;
+      },
+    }
+  }
+  flat
+}
+
+impl<'a> Display for MsytDiff<'a> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    for section in &self.sections {
+      match *section {
+        SectionChange::Platform(ref c) => writeln!(f, "~ platform: {:?} -> {:?}", c.old, c.new)?,
+        SectionChange::Encoding(ref c) => writeln!(f, "~ encoding: {:?} -> {:?}", c.old, c.new)?,
+        SectionChange::Header(ref c) => writeln!(f, "~ header: {:?} -> {:?}", c.old, c.new)?,
+        SectionChange::GroupCount(ref c) => writeln!(f, "~ group_count: {} -> {}", c.old, c.new)?,
+        SectionChange::Atr1Unknown(ref c) => writeln!(f, "~ atr1_unknown: {:?} -> {:?}", c.old, c.new)?,
+        SectionChange::Ato1(ref c) => writeln!(f, "~ ato1: {} -> {}", DisplayLen(c.old), DisplayLen(c.new))?,
+        SectionChange::Tsy1(ref c) => writeln!(f, "~ tsy1: {} -> {}", DisplayLen(c.old), DisplayLen(c.new))?,
+        SectionChange::Nli1(ref n) => {
+          writeln!(f, "~ nli1")?;
+          if let Some(ref c) = n.id_count {
+            writeln!(f, "    id_count: {:?} -> {:?}", c.old, c.new)?;
+          }
+          for id in &n.added {
+            writeln!(f, "    + global id {}", id)?;
+          }
+          for id in &n.removed {
+            writeln!(f, "    - global id {}", id)?;
+          }
+          for id in &n.changed {
+            writeln!(f, "    ~ global id {}", id)?;
+          }
+        },
+        SectionChange::Unknown { ref magic, ref change } => writeln!(f, "~ {}: {} -> {}", magic.to_lowercase(), DisplayLen(change.old), DisplayLen(change.new))?,
+      }
+    }
+
+    for label in &self.added {
+      writeln!(f, "+ {}", label)?;
+    }
+    for label in &self.removed {
+      writeln!(f, "- {}", label)?;
+    }
+
+    for (label, entry) in &self.changed {
+      writeln!(f, "~ {}", label)?;
+      if let Some(ref c) = entry.attributes {
+        writeln!(f, "    attributes: {:?} -> {:?}", c.old, c.new)?;
+      }
+      if let Some(ref c) = entry.attribute_fields {
+        writeln!(f, "    attribute_fields: {:?} -> {:?}", c.old, c.new)?;
+      }
+      for change in &entry.contents {
+        match *change {
+          ContentChange::Added { index, content } => writeln!(f, "    [{}] + {}", index, DisplayContent(content))?,
+          ContentChange::Removed { index, content } => writeln!(f, "    [{}] - {}", index, DisplayContent(content))?,
+          ContentChange::Changed { old_index, new_index, old, new } => {
+            writeln!(f, "    [{}] - {}", old_index, DisplayContent(old))?;
+            writeln!(f, "    [{}] + {}", new_index, DisplayContent(new))?;
+          },
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+struct DisplayLen(Option<usize>);
+
+impl Display for DisplayLen {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self.0 {
+      Some(len) => write!(f, "{} bytes", len),
+      None => f.write_str("absent"),
+    }
+  }
+}
+
+struct DisplayContent<'a>(&'a Content);
+
+impl<'a> Display for DisplayContent<'a> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match *self.0 {
+      Content::Text(ref s) => write!(f, "text {:?}", s),
+      Content::Control(ref c) => write!(f, "control {:?}", c),
+      Content::Page(ref page) => {
+        f.write_str("page [")?;
+        for (i, content) in page.iter().enumerate() {
+          if i > 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "{}", DisplayContent(content))?;
+        }
+        f.write_str("]")
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn text(s: &str) -> Content {
+    Content::Text(s.to_string())
+  }
+
+  #[test]
+  fn insertion_only_reports_the_inserted_item() {
+    let old = [text("a"), text("b"), text("c")];
+    let new = [text("a"), text("x"), text("b"), text("c")];
+    let changes = ContentChange::between(&old.iter().collect::<Vec<_>>(), &new.iter().collect::<Vec<_>>());
+    match changes[..] {
+      [ContentChange::Added { index: 1, content }] => assert_eq!(*content, text("x")),
+      ref c => panic!("unexpected changes {:?}", c),
+    }
+  }
+
+  #[test]
+  fn replacement_is_reported_as_a_change() {
+    let old = [text("a"), text("b"), text("c"), text("d")];
+    let new = [text("a"), text("x"), text("c")];
+    let changes = ContentChange::between(&old.iter().collect::<Vec<_>>(), &new.iter().collect::<Vec<_>>());
+    match changes[..] {
+      [ContentChange::Changed { old_index: 1, new_index: 1, .. }, ContentChange::Removed { index: 3, .. }] => {},
+      ref c => panic!("unexpected changes {:?}", c),
+    }
+  }
+
+  #[test]
+  fn pages_compare_equal_to_their_page_breaks() {
+    let paged = [Content::Page(vec![text("a")]), Content::Page(vec![text("b")])];
+    let flat = [text("a"), Content::Control(Control::PageBreak), text("b")];
+    assert_eq!(flatten(&paged), flat.iter().collect::<Vec<_>>());
+  }
+}
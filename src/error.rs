@@ -0,0 +1,76 @@
+//! Errors with enough detail to find and reverse-engineer the bytes that caused them.
+
+use failure::Fail;
+
+use std::fmt::{self, Display, Formatter};
+
+/// How many bytes before the failing offset to include in a dump.
+const DUMP_BEFORE: usize = 16;
+/// How many bytes from the failing offset onwards to include in a dump.
+const DUMP_AFTER: usize = 32;
+const DUMP_WIDTH: usize = 16;
+
+/// An error encountered while parsing the control sequences in a TXT2 string.
+#[derive(Debug)]
+pub struct ControlError {
+  /// The label the string belongs to, if known.
+  pub label: Option<String>,
+  /// The offset within the string where parsing failed.
+  pub offset: usize,
+  /// The offset within the string of the first byte in `bytes`.
+  pub bytes_offset: usize,
+  /// The bytes surrounding `offset`.
+  pub bytes: Vec<u8>,
+  cause: failure::Error,
+}
+
+impl ControlError {
+  pub fn new<E: Into<failure::Error>>(s: &[u8], offset: usize, cause: E) -> Self {
+    let offset = offset.min(s.len());
+    let start = offset.saturating_sub(DUMP_BEFORE);
+    let end = (offset + DUMP_AFTER).min(s.len());
+    ControlError {
+      label: None,
+      offset,
+      bytes_offset: start,
+      bytes: s[start..end].to_vec(),
+      cause: cause.into(),
+    }
+  }
+
+  pub fn with_label<S: Into<String>>(mut self, label: S) -> Self {
+    self.label = Some(label.into());
+    self
+  }
+}
+
+impl Fail for ControlError {
+  fn cause(&self) -> Option<&dyn Fail> {
+    Some(self.cause.as_fail())
+  }
+}
+
+impl Display for ControlError {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "could not parse control sequences")?;
+    if let Some(ref label) = self.label {
+      write!(f, " for label {}", label)?;
+    }
+    write!(f, " at byte {:#x}", self.offset)?;
+
+    for (i, line) in self.bytes.chunks(DUMP_WIDTH).enumerate() {
+      let line_offset = self.bytes_offset + i * DUMP_WIDTH;
+      write!(f, "\n  {:06x}:", line_offset)?;
+      for (j, byte) in line.iter().enumerate() {
+        // mark the byte where parsing failed
+        if line_offset + j == self.offset {
+          write!(f, " [{:02x}]", byte)?;
+        } else {
+          write!(f, " {:02x}", byte)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,290 @@
+//! Game profiles, which decide how the control sequences in a TXT2 string are decoded.
+//!
+//! Every game shares the same framing for control sequences: a 0x0e marker, the control type and
+//! then type-specific parameters. Each game's [`Profile`] supplies the table of control types it
+//! knows and their friendly [`Control`] mappings, or an MSBP describes them. Anything a profile
+//! does not know is kept as [`Control::Unknown`].
+
+use crate::{
+  Result,
+  botw::{Control, MainControl, zero::Control0},
+  error::ControlError,
+  model::Content,
+  msbp::Msbp,
+};
+
+use byteordered::Endian;
+use failure::ResultExt;
+use msbt::{Encoding, Header};
+
+use std::{
+  io::Write,
+  sync::Arc,
+};
+
+/// Parses a control sequence of one type. `buf` starts after the control type.
+pub type ControlParser = fn(&Header, &[u8]) -> Result<(usize, Control)>;
+
+/// The control sequences one game uses.
+///
+/// Each game supplies a table of the control types it knows and the parser that maps each one to
+/// its friendly [`Control`]s. Implement this to decode the controls of a game msyt has no profile
+/// for, and use it with [`Game::Custom`].
+pub trait Profile: std::fmt::Debug + Send + Sync {
+  /// The control types this game decodes, with their parsers.
+  fn controls(&self) -> &[(u16, ControlParser)];
+
+  /// Parse a control sequence of type `kind`. `buf` starts after the control type.
+  ///
+  /// Types missing from [`controls`](Profile::controls), and controls that do not fit the layout
+  /// their parser expects, are kept as [`Control::Unknown`].
+  fn parse_control(&self, header: &Header, kind: u16, buf: &[u8]) -> Result<(usize, Control)> {
+    match self.controls().iter().find(|&&(k, _)| k == kind) {
+      Some(&(_, parse)) => parse(header, buf).or_else(|e| {
+        Control::parse_unknown(header, kind, buf)
+          .map_err(|_| e.context(format!("could not parse control sequence {}", kind)).into())
+      }),
+      None => Control::parse_unknown(header, kind, buf)
+        .with_context(|_| "could not parse unknown control sequence")
+        .map_err(Into::into),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum Game {
+  /// The Legend of Zelda: Breath of the Wild.
+  #[default]
+  Botw,
+  /// The Legend of Zelda: Tears of the Kingdom.
+  Totk,
+  /// Splatoon 2.
+  Splatoon2,
+  /// Mario Kart 8 and Mario Kart 8 Deluxe.
+  Mk8,
+  /// No game in particular. Every control sequence is kept as raw data, which is safe for any
+  /// game whose controls msyt does not know.
+  Generic,
+  /// Any game, with the tags described by the game's MSBP.
+  Project(Arc<Msbp>),
+  /// Any game, with the controls decoded by a profile supplied by the caller.
+  Custom(Arc<dyn Profile>),
+}
+
+impl Game {
+  pub const NAMES: &'static [&'static str] = &["botw", "totk", "splatoon2", "mk8", "generic"];
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    let game = match name {
+      "botw" => Game::Botw,
+      "totk" => Game::Totk,
+      "splatoon2" => Game::Splatoon2,
+      "mk8" => Game::Mk8,
+      "generic" => Game::Generic,
+      _ => return None,
+    };
+    Some(game)
+  }
+
+  /// The profile that decodes this game's control sequences.
+  pub fn profile(&self) -> &dyn Profile {
+    match *self {
+      Game::Botw => &crate::botw::Botw,
+      Game::Totk => &crate::totk::Totk,
+      Game::Splatoon2 => &crate::splatoon2::Splatoon2,
+      Game::Mk8 => &crate::mk8::Mk8,
+      Game::Generic => &Generic,
+      Game::Project(ref msbp) => &**msbp,
+      Game::Custom(ref profile) => &**profile,
+    }
+  }
+
+  /// Parse the text and control sequences in a TXT2 string.
+  ///
+  /// Control sequences start with a 0x0e marker the width of one code unit in the MSBT's encoding,
+  /// followed by the control type and its parameters.
+  ///
+  /// Errors carry the offset of the sequence that could not be parsed and the bytes around it.
+  pub fn parse_controls(&self, header: &Header, s: &[u8]) -> std::result::Result<Vec<Content>, ControlError> {
+    let unit = code_unit_len(header);
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while pos + unit <= s.len() {
+      let u = read_code_unit(header, &s[pos..pos + unit])
+        .with_context(|_| "could not read control sequence marker")
+        .map_err(|e| ControlError::new(s, pos, e))?;
+      if u != 0x0e {
+        pos += unit;
+        continue;
+      }
+
+      if text_start < pos {
+        let string = parse_text(header, &s[text_start..pos])
+          .map_err(|e| ControlError::new(s, text_start, e))?;
+        parts.push(Content::Text(string));
+      }
+
+      let marker_offset = pos;
+      let kind = s.get(pos + unit..pos + unit + 2)
+        .ok_or_else(|| failure::format_err!("missing control sequence type"))
+        .and_then(|k| header.endianness().read_u16(k).map_err(Into::into))
+        .map_err(|e| ControlError::new(s, marker_offset, e))?;
+      let body = &s[pos + unit + 2..];
+      let (read, ctl) = self.profile().parse_control(header, kind, body)
+        .map_err(|e| ControlError::new(s, marker_offset, e))?;
+      parts.push(Content::Control(ctl));
+
+      pos += unit + 2 + read;
+      text_start = pos;
+    }
+
+    if text_start < s.len() {
+      let string = parse_text(header, &s[text_start..])
+        .map_err(|e| ControlError::new(s, text_start, e))?;
+      let string = crate::util::strip_nul(&string);
+      if !string.is_empty() {
+        parts.push(Content::Text(string.to_string()));
+      }
+    }
+
+    Ok(parts)
+  }
+}
+
+/// Decodes no control sequences.
+#[derive(Debug)]
+struct Generic;
+
+impl Profile for Generic {
+  fn controls(&self) -> &[(u16, ControlParser)] {
+    &[]
+  }
+}
+
+/// Parse a control sequence from the system group, type 0, that games made with LibMessageStudio
+/// share: ruby, text size and page breaks. Its font and colour subtypes take different parameters
+/// in each game, so they are kept as [`Control::Unknown`].
+pub(crate) fn parse_system_control(header: &Header, buf: &[u8]) -> Result<(usize, Control)> {
+  let sub_kind = match buf.get(..2) {
+    Some(sub_kind) => header.endianness().read_u16(sub_kind)?,
+    None => return Control::parse_unknown(header, 0, buf),
+  };
+  match sub_kind {
+    0 | 2 | 4 => Control0::parse(header, buf),
+    _ => Control::parse_unknown(header, 0, buf),
+  }
+}
+
+/// The length in bytes of one code unit in the MSBT's encoding.
+pub(crate) fn code_unit_len(header: &Header) -> usize {
+  match header.encoding() {
+    Encoding::Utf16 => 2,
+    Encoding::Utf8 => 1,
+  }
+}
+
+fn read_code_unit(header: &Header, bytes: &[u8]) -> Result<u16> {
+  match header.encoding() {
+    Encoding::Utf16 => header.endianness().read_u16(bytes).map_err(Into::into),
+    Encoding::Utf8 => Ok(u16::from(bytes[0])),
+  }
+}
+
+/// Write one code unit in the MSBT's encoding.
+pub(crate) fn write_code_unit(header: &Header, writer: &mut dyn Write, u: u16) -> Result<()> {
+  match header.encoding() {
+    Encoding::Utf16 => header.endianness().write_u16(writer, u)?,
+    Encoding::Utf8 => writer.write_all(&[u as u8])?,
+  }
+  Ok(())
+}
+
+/// Encode `s` in the MSBT's encoding.
+pub(crate) fn encode_text(header: &Header, s: &str) -> Vec<u8> {
+  match header.encoding() {
+    Encoding::Utf16 => {
+      let mut buf = Vec::with_capacity(s.len() * 2);
+      for u in s.encode_utf16() {
+        header.endianness().write_u16(&mut buf, u).expect("failed writing to vec");
+      }
+      buf
+    },
+    Encoding::Utf8 => s.as_bytes().to_vec(),
+  }
+}
+
+pub(crate) fn parse_text(header: &Header, s: &[u8]) -> Result<String> {
+  match header.encoding() {
+    Encoding::Utf16 => {
+      let bytes: Vec<u16> = s
+        .chunks(2)
+        .map(|x| header.endianness().read_u16(x)
+          .with_context(|_| "could not read bytes")
+          .map_err(Into::into))
+        .collect::<Result<_>>()?;
+      String::from_utf16(&bytes)
+        .with_context(|_| "could not parse utf-16 string")
+        .map_err(Into::into)
+    },
+    Encoding::Utf8 => String::from_utf8(s.to_vec())
+      .with_context(|_| "could not parse utf-8 string")
+      .map_err(Into::into),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::botw::Icon;
+
+  use byteordered::Endianness;
+  use msbt::builder::MsbtBuilder;
+
+  /// Reads icons like Breath of the Wild, and nothing else.
+  #[derive(Debug)]
+  struct IconsOnly;
+
+  impl Profile for IconsOnly {
+    fn controls(&self) -> &[(u16, ControlParser)] {
+      &[
+        (0x01, crate::botw::one::Control1::parse),
+      ]
+    }
+  }
+
+  fn controls(game: &Game, controls: &[Control]) -> Vec<Content> {
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let mut buf = Vec::new();
+    for control in controls {
+      control.write(builder.header(), &mut buf).unwrap();
+    }
+    game.parse_controls(builder.header(), &buf).unwrap()
+  }
+
+  #[test]
+  fn profiles_decode_only_their_own_controls() {
+    let written = || vec![
+      Control::PageBreak,
+      Control::TextSize { percent: 125 },
+      Control::Icon { icon: Icon::B },
+    ];
+
+    for game in &[Game::Totk, Game::Splatoon2, Game::Mk8] {
+      let parsed = controls(game, &written());
+      assert_eq!(parsed[..2], [Content::Control(Control::PageBreak), Content::Control(Control::TextSize { percent: 125 })]);
+      match parsed[2] {
+        Content::Control(Control::Unknown { kind: 1, sub_kind: 7, .. }) => {},
+        ref c => panic!("expected an unknown control, got {:?}", c),
+      }
+    }
+
+    let parsed = controls(&Game::Custom(Arc::new(IconsOnly)), &written());
+    assert!(matches!(parsed[0], Content::Control(Control::Unknown { kind: 0, sub_kind: 4, .. })));
+    assert_eq!(parsed[2], Content::Control(Control::Icon { icon: Icon::B }));
+
+    assert_eq!(controls(&Game::Botw, &written()), written().into_iter().map(Content::Control).collect::<Vec<_>>());
+  }
+}
@@ -0,0 +1,299 @@
+use crate::{
+  Result,
+  model::{Content, Msyt},
+  raw::{self, RawMsbt},
+};
+
+use failure::ResultExt;
+use msbt::{
+  Msbt,
+  builder::MsbtBuilder,
+  section::{Ato1, Atr1, Nli1, Tsy1},
+};
+
+use std::{
+  collections::BTreeMap,
+  io::Cursor,
+  pin::Pin,
+};
+
+/// A label being carried over into a rebuilt MSBT.
+struct ImportedLabel {
+  name: String,
+  value: Vec<u8>,
+  attributes: Option<String>,
+}
+
+impl Msyt {
+  /// Import this MSYT into an existing MSBT.
+  ///
+  /// The values of labels that already exist in the MSBT are replaced in place. If the MSYT
+  /// contains labels that the MSBT does not or changes any attributes, the MSBT is rebuilt with
+  /// the new labels appended after the existing ones.
+  ///
+  /// Returns the labels in the MSBT that are missing from the MSYT. If `prune` is true, these
+  /// labels are removed from the MSBT.
+  pub fn import_into(self, msbt: &mut Pin<Box<Msbt>>, prune: bool) -> Result<Vec<String>> {
+    if self.entries.values().any(|e| e.attribute_fields.is_some()) {
+      failure::bail!("attribute_fields cannot be imported: create the msbt instead");
+    }
+    let lbl1 = match msbt.lbl1() {
+      Some(lbl1) => lbl1,
+      None => failure::bail!("invalid msbt: missing lbl1"),
+    };
+    let mut missing: Vec<_> = lbl1.labels()
+      .iter()
+      .filter(|l| !self.entries.contains_key(l.name()))
+      .collect();
+    missing.sort_by_key(|l| l.index());
+    let missing: Vec<String> = missing.into_iter().map(|l| l.name().to_string()).collect();
+
+    let needs_rebuild = (prune && !missing.is_empty()) || self.entries.iter().any(|(key, entry)| {
+      let label = match lbl1.labels().iter().find(|l| l.name() == key) {
+        Some(l) => l,
+        None => return true,
+      };
+      let attributes = msbt.atr1()
+        .and_then(|a| a.strings()
+          .get(label.index() as usize)
+          .map(|s| crate::util::strip_nul(s).to_string()));
+      entry.attributes.is_some() && entry.attributes != attributes
+    });
+
+    if needs_rebuild {
+      *msbt = self.rebuild(msbt, prune)?;
+      return Ok(missing);
+    }
+
+    for (key, entry) in self.entries {
+      let new_val = Content::write_all(msbt.header(), &entry.contents)
+        .with_context(|_| format!("could not write contents for label {}", key))?;
+      if let Some(ref mut lbl1) = msbt.lbl1_mut() {
+        if let Some(label) = lbl1.labels_mut().iter_mut().find(|x| x.name() == key) {
+          if let Err(()) = label.set_value_raw(new_val) {
+            failure::bail!("could not set raw string at index {}", label.index());
+          }
+        }
+      }
+    }
+
+    Ok(missing)
+  }
+
+  /// Import this MSYT into the MSBT contained in `bytes`, keeping the original section order.
+  ///
+  /// Returns the new MSBT and the labels missing from the MSYT, as in [`Msyt::import_into`].
+  pub fn import_into_bytes(self, bytes: &[u8], prune: bool) -> Result<(Vec<u8>, Vec<String>)> {
+    let original = RawMsbt::parse(bytes).with_context(|_| "could not read msbt sections")?;
+    let (known, unknown_sections) = raw::split_unknown_sections(bytes)?;
+    let mut msbt = Msbt::from_reader(Cursor::new(&*known)).with_context(|_| "could not read msbt")?;
+
+    let missing = self.import_into(&mut msbt, prune)?;
+
+    let mut buf = Vec::new();
+    msbt.write_to(&mut buf).with_context(|_| "could not write msbt")?;
+
+    let mut imported = RawMsbt::parse(&buf).with_context(|_| "could not read imported msbt sections")?;
+    imported.header = original.header;
+    imported.sections.extend(unknown_sections);
+    imported.reorder(&original.section_order());
+    Ok((imported.to_bytes()?, missing))
+  }
+
+  /// Build a new MSBT from `msbt`, replacing the values and attributes of existing labels and
+  /// appending new ones. If `prune` is true, labels that are not in this MSYT are left out.
+  fn rebuild(self, msbt: &Msbt, prune: bool) -> Result<Pin<Box<Msbt>>> {
+    let lbl1 = msbt.lbl1().ok_or_else(|| failure::format_err!("invalid msbt: missing lbl1"))?;
+    let header = msbt.header();
+
+    let mut labels: Vec<ImportedLabel> = {
+      let mut existing: Vec<_> = lbl1.labels().iter().collect();
+      existing.sort_by_key(|l| l.index());
+      existing
+        .into_iter()
+        .map(|l| -> Result<ImportedLabel> {
+          Ok(ImportedLabel {
+            name: l.name().to_string(),
+            value: l.value_raw()
+              .ok_or_else(|| failure::format_err!("invalid msbt: missing string for label {}", l.name()))?
+              .to_vec(),
+            attributes: msbt.atr1()
+              .and_then(|a| a.strings()
+                .get(l.index() as usize)
+                .map(|s| crate::util::strip_nul(s).to_string())),
+          })
+        })
+        .collect::<Result<_>>()?
+    };
+    let original_len = labels.len();
+    let pruned: Vec<bool> = labels.iter()
+      .map(|l| prune && !self.entries.contains_key(&l.name))
+      .collect();
+    let has_attributes = msbt.atr1().map(|a| !a.strings().is_empty()).unwrap_or(false);
+
+    for (key, entry) in self.entries {
+      let value = Content::write_all(header, &entry.contents)
+        .with_context(|_| format!("could not write contents for label {}", key))?;
+      match labels.iter_mut().find(|l| l.name == key) {
+        Some(label) => {
+          label.value = value;
+          if entry.attributes.is_some() {
+            label.attributes = entry.attributes;
+          }
+        },
+        None => labels.push(ImportedLabel {
+          name: key,
+          value,
+          attributes: match entry.attributes {
+            Some(a) => Some(a),
+            None if has_attributes => Some(String::new()),
+            None => None,
+          },
+        }),
+      }
+    }
+
+    // map the TXT2 index of every original label to its index after pruning
+    let mut new_indices = Vec::with_capacity(original_len);
+    let mut next_index = 0;
+    for &p in &pruned {
+      if p {
+        new_indices.push(None);
+      } else {
+        new_indices.push(Some(next_index));
+        next_index += 1;
+      }
+    }
+    let labels: Vec<ImportedLabel> = labels.into_iter()
+      .enumerate()
+      .filter(|(i, _)| !pruned.get(*i).cloned().unwrap_or(false))
+      .map(|(_, l)| l)
+      .collect();
+
+    let mut builder = MsbtBuilder::new(header.endianness(), header.encoding(), Some(lbl1.group_count()));
+    if let Some(ato1) = msbt.ato1() {
+      builder = builder.ato1(Ato1::new_unlinked(ato1.unknown_bytes().to_vec()));
+    }
+    if let Some(atr1) = msbt.atr1() {
+      // As in Msyt::into_msbt, if no label has attributes, ATR1 is declared with the correct amount
+      // of entries but left empty. An ATR1 that was empty in the original can only be filled if
+      // the msyt gives every label attributes, since there is nothing to fill in for the others.
+      let without: Vec<&str> = labels.iter()
+        .filter(|l| l.attributes.is_none())
+        .map(|l| l.name.as_str())
+        .collect();
+      if !without.is_empty() && without.len() < labels.len() {
+        failure::bail!(
+          "the msbt has no attributes, so every label needs attributes for them to be imported, but these labels have none: {}",
+          without.join(", "),
+        );
+      }
+      let strings: Option<Vec<String>> = labels.iter()
+        .map(|l| l.attributes.clone().map(crate::util::append_nul))
+        .collect();
+      builder = builder.atr1(Atr1::new_unlinked(labels.len() as u32, atr1.unknown_1(), strings.unwrap_or_default()));
+    } else {
+      let with: Vec<&str> = labels.iter()
+        .filter(|l| l.attributes.is_some())
+        .map(|l| l.name.as_str())
+        .collect();
+      if !with.is_empty() {
+        failure::bail!("the msbt has no atr1 section, so attributes cannot be imported for these labels: {}", with.join(", "));
+      }
+    }
+    if let Some(tsy1) = msbt.tsy1() {
+      let mut bytes = tsy1.unknown_bytes().to_vec();
+      // TSY1 holds a u32 style index for each string, so pruned labels lose theirs and new labels
+      // get the default style
+      if bytes.len() == original_len * 4 {
+        bytes = bytes.chunks(4)
+          .zip(&pruned)
+          .filter(|(_, &p)| !p)
+          .flat_map(|(style, _)| style.to_vec())
+          .collect();
+        bytes.resize(labels.len() * 4, 0);
+      }
+      builder = builder.tsy1(Tsy1::new_unlinked(bytes));
+    }
+    if let Some(nli1) = msbt.nli1() {
+      let global_ids: BTreeMap<u32, u32> = nli1.global_ids()
+        .iter()
+        .filter_map(|(&id, &index)| match new_indices.get(index as usize) {
+          Some(&Some(new_index)) => Some((id, new_index)),
+          Some(&None) => None,
+          None => Some((id, index)),
+        })
+        .collect();
+      let removed = nli1.global_ids().len() - global_ids.len();
+      builder = builder.nli1(Nli1::new_unlinked(nli1.id_count() - removed as u32, global_ids));
+    }
+    for label in labels {
+      builder = builder.add_label(label.name, label.value);
+    }
+
+    Ok(builder.build())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::game::Game;
+
+  use byteordered::Endianness;
+  use msbt::Encoding;
+
+  fn msyt(entries: &str) -> Msyt {
+    serde_yaml::from_str(&format!("group_count: 1\nentries:\n{}", entries)).unwrap()
+  }
+
+  fn msbt() -> Vec<u8> {
+    msyt("  a:\n    contents:\n      - text: one\n  b:\n    contents:\n      - text: two\n")
+      .into_msbt_bytes(Endianness::Little, Encoding::Utf16)
+      .unwrap()
+  }
+
+  fn texts(msbt: &[u8]) -> Vec<(String, String)> {
+    Msyt::from_msbt_bytes(msbt, &Game::Botw).unwrap()
+      .entries
+      .into_iter()
+      .map(|(name, entry)| match entry.contents.as_slice() {
+        [Content::Text(text)] => (name, text.clone()),
+        c => panic!("expected text, got {:?}", c),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn new_labels_are_appended() {
+    let update = msyt("  b:\n    contents:\n      - text: changed\n  c:\n    contents:\n      - text: three\n");
+    let (imported, missing) = update.import_into_bytes(&msbt(), false).unwrap();
+
+    assert_eq!(missing, vec!["a".to_string()]);
+    assert_eq!(texts(&imported), vec![
+      ("a".to_string(), "one".to_string()),
+      ("b".to_string(), "changed".to_string()),
+      ("c".to_string(), "three".to_string()),
+    ]);
+  }
+
+  #[test]
+  fn pruned_labels_are_removed() {
+    let update = msyt("  b:\n    contents:\n      - text: two\n");
+    let (imported, missing) = update.import_into_bytes(&msbt(), true).unwrap();
+
+    assert_eq!(missing, vec!["a".to_string()]);
+    assert_eq!(texts(&imported), vec![("b".to_string(), "two".to_string())]);
+  }
+
+  #[test]
+  fn unchanged_import_keeps_the_msbt() {
+    let original = msbt();
+    let update = Msyt::from_msbt_bytes(&original, &Game::Botw).unwrap();
+    let (imported, missing) = update.import_into_bytes(&original, false).unwrap();
+
+    assert!(missing.is_empty());
+    assert_eq!(imported, original);
+  }
+}
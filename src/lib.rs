@@ -0,0 +1,22 @@
+#![warn(bare_trait_objects)]
+
+pub mod archive;
+pub mod botw;
+pub mod convert;
+pub mod diff;
+pub mod error;
+pub mod game;
+mod import;
+pub mod mk8;
+pub mod model;
+pub mod msbf;
+pub mod msbp;
+mod raw;
+pub mod splatoon2;
+pub mod totk;
+pub mod verify;
+mod util;
+
+pub use crate::model::Msyt;
+
+pub type Result<T> = std::result::Result<T, failure::Error>;
@@ -1,12 +1,9 @@
 #![warn(bare_trait_objects)]
 
-mod botw;
 mod cli;
-mod model;
 mod subcommand;
-mod util;
 
-pub type Result<T> = std::result::Result<T, failure::Error>;
+use msyt::Result;
 
 fn main() {
   std::process::exit(match inner() {
@@ -16,7 +13,7 @@ fn main() {
       eprintln!();
       eprintln!("{}", e);
       for (indent, err) in e.iter_causes().enumerate() {
-        let indent_str: String = std::iter::repeat("  ").take(indent + 1).collect();
+        let indent_str: String = "  ".repeat(indent + 1);
         eprintln!("{}{}", indent_str, err);
       }
       1
@@ -31,6 +28,10 @@ fn inner() -> Result<()> {
     ("export", Some(sub_matches)) => self::subcommand::export(sub_matches),
     ("import", Some(sub_matches)) => self::subcommand::import(sub_matches),
     ("create", Some(sub_matches)) => self::subcommand::create(sub_matches),
+    ("convert", Some(sub_matches)) => self::subcommand::convert(sub_matches),
+    ("diff", Some(sub_matches)) => self::subcommand::diff(sub_matches),
+    ("verify", Some(sub_matches)) => self::subcommand::verify(sub_matches),
+    ("msbf", Some(sub_matches)) => self::subcommand::msbf(sub_matches),
     _ => unreachable!("clap allowed an unspecified subcommand"),
   }
 }
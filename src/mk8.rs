@@ -0,0 +1,19 @@
+//! Control sequences known from Mario Kart 8 and Mario Kart 8 Deluxe.
+//!
+//! The system controls (type 0) that LibMessageStudio games share are decoded: ruby, text size and
+//! page breaks. Every other control sequence is kept as
+//! [`Control::Unknown`](crate::botw::Control::Unknown).
+
+use crate::game::{ControlParser, Profile, parse_system_control};
+
+/// The control sequences of Mario Kart 8 and Mario Kart 8 Deluxe.
+#[derive(Debug)]
+pub struct Mk8;
+
+impl Profile for Mk8 {
+  fn controls(&self) -> &[(u16, ControlParser)] {
+    &[
+      (0x00, parse_system_control),
+    ]
+  }
+}
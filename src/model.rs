@@ -1,24 +1,308 @@
 use crate::{
   Result,
   botw::Control,
+  game::Game,
+  msbp::Attribute,
+  raw::{RawMsbt, RawSection},
 };
 
-use byteordered::Endian;
+use byteordered::{Endian, Endianness};
+use failure::ResultExt;
 use indexmap::IndexMap;
-use msbt::{Encoding, Header};
+use msbt::{
+  Encoding,
+  Header,
+  Msbt,
+  builder::MsbtBuilder,
+  section::Atr1,
+};
 use serde_derive::{Deserialize, Serialize};
 
-use std::collections::BTreeMap;
+use std::{
+  collections::BTreeMap,
+  io::{Cursor, Read, Seek},
+  pin::Pin,
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Msyt {
   #[serde(flatten)]
   pub msbt: MsbtInfo,
   pub entries: IndexMap<String, Entry>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Msyt {
+  /// Convert a parsed MSBT into an MSYT, decoding the control sequences of every label with the
+  /// controls of `game`.
+  ///
+  /// MSBTs without a LBL1 section can only be converted with [`Msyt::from_msbt_bytes`].
+  ///
+  /// Attributes are kept as strings even if `game` is [`Game::Project`]: the msbt crate does not
+  /// keep the raw ATR1 records, so only [`Msyt::from_msbt_bytes`] can decode them into
+  /// `attribute_fields`.
+  pub fn from_msbt(msbt: &Msbt, game: &Game) -> Result<Self> {
+    let lbl1 = match msbt.lbl1() {
+      Some(lbl) => lbl,
+      None => failure::bail!("invalid msbt: missing lbl1"),
+    };
+
+    let mut entries = IndexMap::with_capacity(lbl1.labels().len());
+
+    // keep the labels in the same order as their strings in TXT2
+    let mut labels: Vec<_> = lbl1.labels().iter().collect();
+    labels.sort_by_key(|l| l.index());
+
+    for label in labels {
+      let mut all_content = Vec::new();
+
+      let raw_value = label.value_raw()
+        .ok_or_else(|| failure::format_err!(
+          "invalid msbt: missing string for label {}",
+          label.name(),
+        ))?;
+      let mut parts = game.parse_controls(msbt.header(), raw_value)
+        .map_err(|e| e.with_label(label.name()))?;
+      all_content.append(&mut parts);
+      let entry = Entry {
+        attributes: attributes(msbt, label.index()),
+        attribute_fields: None,
+        contents: all_content,
+        index: None,
+      };
+      entries.insert(label.name().to_string(), entry);
+    }
+
+    Ok(Msyt {
+      entries,
+      msbt: MsbtInfo::from_msbt(msbt, lbl1.group_count()),
+    })
+  }
+
+  /// Convert an MSBT without a LBL1 section into an MSYT, naming each entry after the index of its
+  /// string in TXT2.
+  fn from_unlabelled_msbt(msbt: &Msbt, raw: &RawMsbt, game: &Game) -> Result<Self> {
+    let strings = raw.txt2_strings()?;
+
+    let mut entries = IndexMap::with_capacity(strings.len());
+    for (i, value) in strings.into_iter().enumerate() {
+      let name = format!("#{}", i);
+      let contents = game.parse_controls(msbt.header(), value)
+        .map_err(|e| e.with_label(name.clone()))?;
+      let entry = Entry {
+        attributes: attributes(msbt, i as u32),
+        attribute_fields: None,
+        contents,
+        index: None,
+      };
+      entries.insert(name, entry);
+    }
+
+    let mut info = MsbtInfo::from_msbt(msbt, 0);
+    info.no_lbl1 = true;
+    Ok(Msyt {
+      entries,
+      msbt: info,
+    })
+  }
+
+  /// Sort the entries by label, recording the original position of each so that
+  /// [`Msyt::into_msbt`] can restore it.
+  pub fn sort_entries(&mut self) {
+    for (i, entry) in self.entries.values_mut().enumerate() {
+      if entry.index.is_none() {
+        entry.index = Some(i as u32);
+      }
+    }
+    self.entries.sort_keys();
+  }
+
+  /// Split the contents of every entry that has page breaks into pages, one for each text box.
+  pub fn split_pages(&mut self) {
+    for entry in self.entries.values_mut() {
+      if !entry.contents.iter().any(|c| *c == Content::Control(Control::PageBreak)) {
+        continue;
+      }
+      let mut pages = vec![Vec::new()];
+      for content in entry.contents.drain(..) {
+        match content {
+          Content::Control(Control::PageBreak) => pages.push(Vec::new()),
+          c => pages.last_mut().expect("pages start with one page").push(c),
+        }
+      }
+      entry.contents = pages.into_iter().map(Content::Page).collect();
+    }
+  }
+
+  /// Restore the original order of the entries if any have an index.
+  fn sort_by_index(&mut self) {
+    if self.entries.values().any(|e| e.index.is_some()) {
+      self.entries.sort_by(|_, a, _, b| a.index.unwrap_or(u32::MAX).cmp(&b.index.unwrap_or(u32::MAX)));
+    }
+  }
+
+  /// Build an MSBT with the given byte order and text encoding from this MSYT.
+  ///
+  /// Labels are added in the order of their `index` if any entries have one, with entries
+  /// without an index after those that do. Otherwise they are added in the order of the entries.
+  pub fn into_msbt(mut self, endianness: Endianness, encoding: Encoding) -> Result<Pin<Box<Msbt>>> {
+    self.sort_by_index();
+
+    // without LBL1, the labels are only used to order TXT2 and the LBL1 section is removed in
+    // into_msbt_bytes, but the builder still needs at least one group to hash them into
+    let group_count = if self.msbt.no_lbl1 { 1 } else { self.msbt.group_count };
+    let mut builder = MsbtBuilder::new(endianness, encoding, Some(group_count));
+    if let Some(unknown_bytes) = self.msbt.ato1 {
+      builder = builder.ato1(msbt::section::Ato1::new_unlinked(unknown_bytes));
+    }
+    if let Some(unknown_1) = self.msbt.atr1_unknown {
+      // ATR1 should have exactly the same amount of entries as TXT2. In the BotW files, sometimes
+      // an ATR1 section is specified to have that amount but the section is actually empty. For
+      // msyt's purposes, if the msyt does not contain the same amount of attributes as it does
+      // text entries (i.e. not every label has an `attributes` node), it will be assumed that the
+      // ATR1 section should specify that it has the correct amount of entries but actually be
+      // empty.
+      let strings: Option<Vec<String>> = self.entries
+        .iter()
+        .map(|(_, e)| e.attributes.clone())
+        .map(|s| s.map(crate::util::append_nul))
+        .collect();
+      let atr_len = match strings {
+        Some(ref s) => s.len(),
+        None => self.entries.len(),
+      };
+      let strings = strings.unwrap_or_default();
+      builder = builder.atr1(msbt::section::Atr1::new_unlinked(atr_len as u32, unknown_1, strings));
+    }
+    if let Some(unknown_bytes) = self.msbt.tsy1 {
+      builder = builder.tsy1(msbt::section::Tsy1::new_unlinked(unknown_bytes));
+    }
+    if let Some(nli1) = self.msbt.nli1 {
+      builder = builder.nli1(msbt::section::Nli1::new_unlinked(nli1.id_count, nli1.global_ids));
+    }
+    for (label, entry) in self.entries.into_iter() {
+      let new_val = Content::write_all(builder.header(), &entry.contents)
+        .with_context(|_| format!("could not write contents for label {}", label))?;
+      builder = builder.add_label(label, new_val);
+    }
+
+    Ok(builder.build())
+  }
+
+  /// Read an MSBT from `reader` and convert it into an MSYT.
+  ///
+  /// Unlike [`Msyt::from_msbt`], this also records the MSBT's header and section order.
+  pub fn from_msbt_reader<R: Read + Seek>(mut reader: R, game: &Game) -> Result<Self> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).with_context(|_| "could not read msbt")?;
+    Msyt::from_msbt_bytes(&bytes, game)
+  }
+
+  /// Convert the MSBT contained in `bytes` into an MSYT.
+  ///
+  /// Unlike [`Msyt::from_msbt`], this also records the MSBT's header and section order, and keeps
+  /// any sections that the msbt crate does not read.
+  pub fn from_msbt_bytes(bytes: &[u8], game: &Game) -> Result<Self> {
+    let mut raw = RawMsbt::parse(bytes).with_context(|_| "could not read msbt sections")?;
+    let header = HeaderInfo::from_raw(&raw);
+    let unknown_sections = raw.take_unknown_sections();
+    let msbt = if unknown_sections.is_empty() {
+      Msbt::from_reader(Cursor::new(bytes))
+    } else {
+      Msbt::from_reader(Cursor::new(raw.to_bytes()?))
+    }.with_context(|_| "could not read msbt")?;
+
+    let mut msyt = match msbt.lbl1() {
+      Some(_) => Msyt::from_msbt(&msbt, game)?,
+      None => Msyt::from_unlabelled_msbt(&msbt, &raw, game)?,
+    };
+    if let (Game::Project(msbp), Some(atr1)) = (game, raw.section(b"ATR1")) {
+      let fields = msbp.decode_attributes(msbt.header(), &atr1.data)
+        .with_context(|_| "could not decode attributes")?;
+      if let Some(fields) = fields {
+        // entries are in TXT2 order, which is also the order of the ATR1 records
+        for (entry, fields) in msyt.entries.values_mut().zip(fields) {
+          entry.attributes = None;
+          entry.attribute_fields = Some(fields);
+        }
+      }
+    }
+    msyt.msbt.header = Some(header);
+    msyt.msbt.unknown_sections = unknown_sections
+      .into_iter()
+      .map(|s| (String::from_utf8_lossy(&s.magic).into_owned(), SectionData(s.data)))
+      .collect();
+    Ok(msyt)
+  }
+
+  /// Build an MSBT from this MSYT and return its serialised bytes.
+  ///
+  /// If the MSYT has header information, it is applied to the MSBT and the sections are written
+  /// in the recorded order. Unknown sections are written back as they were. If the MSYT was
+  /// exported from an MSBT without a LBL1 section, the created MSBT has none either. ATR1 is
+  /// written from the entries' `attribute_fields` if any have them.
+  pub fn into_msbt_bytes(mut self, endianness: Endianness, encoding: Encoding) -> Result<Vec<u8>> {
+    let header = self.msbt.header.take();
+    let unknown_sections = std::mem::replace(&mut self.msbt.unknown_sections, IndexMap::new());
+    let no_lbl1 = self.msbt.no_lbl1;
+    let atr1_size = self.msbt.atr1_unknown;
+    let attribute_fields: Vec<_> = if self.entries.values().any(|e| e.attribute_fields.is_some()) {
+      self.sort_by_index();
+      self.entries.values_mut().map(|e| e.attribute_fields.take()).collect()
+    } else {
+      Vec::new()
+    };
+    let msbt = self.into_msbt(endianness, encoding)?;
+    let mut buf = Vec::new();
+    msbt.write_to(&mut buf).with_context(|_| "could not write msbt")?;
+
+    if header.is_none() && unknown_sections.is_empty() && !no_lbl1 && attribute_fields.is_empty() {
+      return Ok(buf);
+    }
+
+    let mut raw = RawMsbt::parse(&buf).with_context(|_| "could not read created msbt sections")?;
+    if no_lbl1 {
+      raw.sections.retain(|s| s.magic != *b"LBL1");
+    }
+    if !attribute_fields.is_empty() {
+      let size = match atr1_size {
+        Some(s) => s,
+        None => failure::bail!("atr1_unknown, the size of each attribute record, is needed to write attribute_fields"),
+      };
+      let fields: Vec<_> = attribute_fields.iter().map(Option::as_ref).collect();
+      let data = crate::msbp::encode_attributes(msbt.header(), size, &fields)
+        .with_context(|_| "could not write attributes")?;
+      match raw.sections.iter_mut().find(|s| s.magic == *b"ATR1") {
+        Some(atr1) => atr1.data = data,
+        None => raw.sections.push(RawSection {
+          magic: *b"ATR1",
+          data,
+        }),
+      }
+    }
+    for (magic, data) in unknown_sections {
+      raw.sections.push(RawSection {
+        magic: section_magic(&magic)?,
+        data: data.0,
+      });
+    }
+    if let Some(header) = header {
+      header.apply(&mut raw)?;
+    }
+    raw.to_bytes()
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct MsbtInfo {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub platform: Option<Platform>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub encoding: Option<TextEncoding>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub header: Option<HeaderInfo>,
+  /// Whether the MSBT has no LBL1 section, in which case entries are named `#<index>`.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub no_lbl1: bool,
   pub group_count: u32,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub atr1_unknown: Option<u32>,
@@ -28,33 +312,197 @@ pub struct MsbtInfo {
   pub tsy1: Option<Vec<u8>>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub nli1: Option<Nli1>,
+  /// Sections that msyt does not understand, keyed by magic.
+  #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+  pub unknown_sections: IndexMap<String, SectionData>,
+}
+
+impl MsbtInfo {
+  fn from_msbt(msbt: &Msbt, group_count: u32) -> Self {
+    MsbtInfo {
+      platform: Some(Platform::from_endianness(msbt.header().endianness())),
+      encoding: Some(msbt.header().encoding().into()),
+      header: None,
+      no_lbl1: false,
+      group_count,
+      atr1_unknown: msbt.atr1().map(Atr1::unknown_1),
+      ato1: msbt.ato1().map(|a| a.unknown_bytes().to_vec()),
+      tsy1: msbt.tsy1().map(|a| a.unknown_bytes().to_vec()),
+      nli1: msbt.nli1().map(|a| Nli1 {
+        id_count: a.id_count(),
+        global_ids: a.global_ids().clone(),
+      }),
+      unknown_sections: IndexMap::new(),
+    }
+  }
+}
+
+fn attributes(msbt: &Msbt, index: u32) -> Option<String> {
+  msbt.atr1()
+    .and_then(|a| a.strings()
+      .get(index as usize)
+      .map(|s| crate::util::strip_nul(s))
+      .map(ToString::to_string))
+}
+
+/// The raw contents of a section, serialised as base64.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct SectionData(#[serde(with = "crate::util::serde_base64")] pub Vec<u8>);
+
+/// Parts of the MSBT header and layout that are otherwise chosen when the MSBT is created.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HeaderInfo {
+  pub version: u8,
+  /// The header bytes with no known meaning, if any are not zero.
+  #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::util::option_serde_base64")]
+  pub unknown: Option<Vec<u8>>,
+  /// The magic of each section in the order they appear.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub section_order: Vec<String>,
+}
+
+impl HeaderInfo {
+  pub(crate) fn from_raw(raw: &RawMsbt) -> Self {
+    let unknown = raw.unknown_header_bytes();
+    HeaderInfo {
+      version: raw.version(),
+      unknown: if unknown.iter().all(|&b| b == 0) { None } else { Some(unknown) },
+      section_order: raw.section_order()
+        .iter()
+        .map(|m| String::from_utf8_lossy(m).into_owned())
+        .collect(),
+    }
+  }
+
+  pub(crate) fn apply(&self, raw: &mut RawMsbt) -> Result<()> {
+    raw.set_version(self.version);
+    if let Some(ref unknown) = self.unknown {
+      raw.set_unknown_header_bytes(unknown).with_context(|_| "invalid header in msyt")?;
+    }
+
+    let order = self.section_order.iter()
+      .map(|m| section_magic(m))
+      .collect::<Result<Vec<_>>>()?;
+    raw.reorder(&order);
+
+    Ok(())
+  }
+}
+
+pub(crate) fn section_magic(s: &str) -> Result<[u8; 4]> {
+  let mut magic = [0; 4];
+  if s.len() != magic.len() {
+    failure::bail!("invalid section magic in msyt: {}", s);
+  }
+  magic.copy_from_slice(s.as_bytes());
+  Ok(magic)
+}
+
+/// The platform an MSBT was made for, which determines its byte order.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+  Switch,
+  #[serde(rename = "wiiu")]
+  WiiU,
+}
+
+impl Platform {
+  pub fn from_endianness(endianness: Endianness) -> Self {
+    match endianness {
+      Endianness::Little => Platform::Switch,
+      Endianness::Big => Platform::WiiU,
+    }
+  }
+
+  pub fn endianness(self) -> Endianness {
+    match self {
+      Platform::Switch => Endianness::Little,
+      Platform::WiiU => Endianness::Big,
+    }
+  }
+}
+
+/// The text encoding of an MSBT.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+  Utf16,
+  Utf8,
+}
+
+impl From<Encoding> for TextEncoding {
+  fn from(encoding: Encoding) -> Self {
+    match encoding {
+      Encoding::Utf16 => TextEncoding::Utf16,
+      Encoding::Utf8 => TextEncoding::Utf8,
+    }
+  }
+}
+
+impl From<TextEncoding> for Encoding {
+  fn from(encoding: TextEncoding) -> Self {
+    match encoding {
+      TextEncoding::Utf16 => Encoding::Utf16,
+      TextEncoding::Utf8 => Encoding::Utf8,
+    }
+  }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Entry {
+  /// The position of this label's string in TXT2, recorded when the entries are sorted.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub index: Option<u32>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub attributes: Option<String>,
+  /// Attributes decoded with an MSBP. If any entry has these, ATR1 is written from them instead of
+  /// from `attributes`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub attribute_fields: Option<IndexMap<String, Attribute>>,
   pub contents: Vec<Content>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Nli1 {
   pub id_count: u32,
   pub global_ids: BTreeMap<u32, u32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Content {
   Text(String),
   Control(Control),
+  /// One text box. Consecutive pages are separated by page breaks when written.
+  Page(Vec<Content>),
 }
 
 impl Content {
   pub fn write_all(header: &Header, contents: &[Content]) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
+    Content::write_contents(header, contents, &mut buf)?;
+
+    // add \u0000
+    crate::game::write_code_unit(header, &mut buf, 0)?;
+
+    Ok(buf)
+  }
 
+  fn write_contents(header: &Header, contents: &[Content], buf: &mut Vec<u8>) -> Result<()> {
+    let mut previous_page = false;
     for content in contents {
+      if let Content::Page(ref page) = *content {
+        if previous_page {
+          Control::PageBreak.write(header, buf)?;
+        }
+        Content::write_contents(header, page, buf)?;
+        previous_page = true;
+        continue;
+      }
+      previous_page = false;
+
       match *content {
         Content::Text(ref s) => match header.encoding() {
           Encoding::Utf16 => {
@@ -69,14 +517,11 @@ impl Content {
           }
           Encoding::Utf8 => buf.append(&mut s.as_bytes().to_vec()),
         },
-        Content::Control(ref c) => c.write(header, &mut buf)?,
+        Content::Control(ref c) => c.write(header, buf)?,
+        Content::Page(_) => unreachable!("pages are written above"),
       }
     }
 
-    // add \u0000
-    buf.push(0);
-    buf.push(0);
-
-    Ok(buf)
+    Ok(())
   }
 }
@@ -0,0 +1,511 @@
+//! MSBF flowcharts, which drive the branching dialogue of the MSBT they are paired with.
+//!
+//! An MSBF has the same header and section framing as an MSBT. FLW2 holds the flow nodes followed
+//! by a table of branch targets, and FEN1 names the entry points of each flow. Other sections are
+//! kept as-is.
+//!
+//! Each node is 16 bytes: a u16 type followed by seven u16 fields. Only the fields whose meaning is
+//! known are named; the others are kept as `field_<n>`, numbered by position, so that they can be
+//! written back.
+
+use crate::{
+  Msyt,
+  Result,
+  botw::Control,
+  model::{Content, HeaderInfo, Platform, SectionData, TextEncoding},
+  raw::{self, RawMsbt, RawSection},
+};
+
+use byteordered::{Endian, Endianness};
+use failure::ResultExt;
+use indexmap::IndexMap;
+use serde_derive::{Deserialize, Serialize};
+
+const MAGIC: &[u8] = b"MsgFlwBn";
+const NODE_LEN: usize = 0x10;
+/// The value of a node reference that points nowhere.
+const NO_NODE: u16 = 0xFFFF;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Msbf {
+  pub platform: Platform,
+  pub encoding: TextEncoding,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub header: Option<HeaderInfo>,
+  /// The number of hash groups in FEN1.
+  pub group_count: u32,
+  /// The name of each flow mapped to the index of its entry node.
+  pub flows: IndexMap<String, u32>,
+  /// The nodes of every flow. Nodes refer to each other by their index in this list.
+  pub nodes: Vec<Node>,
+  /// Sections that msyt does not understand, keyed by magic.
+  #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+  pub unknown_sections: IndexMap<String, SectionData>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Node {
+  /// Shows a message from the paired MSBT.
+  Message {
+    field_1: u16,
+    /// The index of the MSBT the message is in, for flows that use more than one.
+    group: u16,
+    /// The index of the message's string in TXT2.
+    message: u16,
+    /// The message's label. When an MSBF is created with the paired MSYT, `message` is taken from
+    /// this label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<u16>,
+    field_5: u16,
+    field_6: u16,
+    field_7: u16,
+  },
+  /// Continues at one of several nodes, depending on a condition or the choice made in the
+  /// message before it.
+  Branch {
+    field_1: u16,
+    field_2: u16,
+    condition: u16,
+    field_4: u16,
+    field_7: u16,
+    branches: Vec<Branch>,
+  },
+  /// Runs a game-specific event.
+  Event {
+    field_1: u16,
+    field_2: u16,
+    event: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<u16>,
+    field_5: u16,
+    field_6: u16,
+    field_7: u16,
+  },
+  /// The start of a flow.
+  Entry {
+    field_1: u16,
+    field_2: u16,
+    field_3: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<u16>,
+    field_5: u16,
+    field_6: u16,
+    field_7: u16,
+  },
+  /// A node of a type msyt does not know.
+  Unknown {
+    node_kind: u16,
+    fields: [u16; 7],
+  },
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Branch {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub next: Option<u16>,
+  /// The choice label that leads to this branch, if the previous message has a choice with one
+  /// option for each branch. For reference; not used when writing.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub choice: Option<u16>,
+}
+
+impl Msbf {
+  /// Parse the MSBF in `bytes`.
+  ///
+  /// If the MSYT of the paired MSBT is given, message nodes record the label of their message and
+  /// branches after a choice record the choice that leads to them.
+  pub fn from_bytes(bytes: &[u8], msyt: Option<&Msyt>) -> Result<Self> {
+    if !bytes.starts_with(MAGIC) {
+      failure::bail!("invalid msbf: bad magic");
+    }
+    let mut raw = RawMsbt::parse(bytes).with_context(|_| "could not read msbf sections")?;
+    let endianness = raw.endianness();
+    let header = HeaderInfo::from_raw(&raw);
+    let encoding = match raw.header[0x0C] {
+      0 => TextEncoding::Utf8,
+      _ => TextEncoding::Utf16,
+    };
+
+    let flw2 = match raw.section(b"FLW2") {
+      Some(s) => s.data.clone(),
+      None => failure::bail!("invalid msbf: missing flw2"),
+    };
+    let nodes = read_nodes(&flw2, endianness).with_context(|_| "could not read flw2")?;
+
+    let (flows, group_count) = match raw.section(b"FEN1") {
+      Some(s) => {
+        let flows = raw::read_labels(&s.data, endianness).with_context(|_| "could not read fen1")?;
+        let group_count = endianness.read_u32(&s.data[..4])?;
+        (flows.into_iter().collect(), group_count)
+      },
+      None => failure::bail!("invalid msbf: missing fen1"),
+    };
+
+    raw.sections.retain(|s| s.magic != *b"FLW2" && s.magic != *b"FEN1");
+    let mut msbf = Msbf {
+      platform: Platform::from_endianness(endianness),
+      encoding,
+      header: Some(header),
+      group_count,
+      flows,
+      nodes,
+      unknown_sections: raw.sections
+        .into_iter()
+        .map(|s| (String::from_utf8_lossy(&s.magic).into_owned(), SectionData(s.data)))
+        .collect(),
+    };
+    if let Some(msyt) = msyt {
+      msbf.resolve(msyt);
+    }
+    Ok(msbf)
+  }
+
+  /// Record the labels of messages and the choices that lead to branches from `msyt`.
+  ///
+  /// Branches after a choice may also depend on a condition, so a branch node whose branch count
+  /// differs from the number of choices records no choices.
+  fn resolve(&mut self, msyt: &Msyt) {
+    // the entries of an exported msyt are in TXT2 order unless they were sorted
+    let entry = |index: u16| msyt.entries
+      .iter()
+      .enumerate()
+      .find(|&(i, (_, e))| e.index.unwrap_or(i as u32) == u32::from(index))
+      .map(|(_, entry)| entry);
+
+    let mut choices = Vec::new();
+    for node in &mut self.nodes {
+      if let Node::Message { message, ref mut label, next, .. } = *node {
+        let (name, e) = match entry(message) {
+          Some(e) => e,
+          None => continue,
+        };
+        *label = Some(name.clone());
+        let choice_labels = e.contents.iter()
+          .flat_map(|c| match *c {
+            Content::Page(ref page) => page.iter().collect::<Vec<_>>(),
+            ref c => vec![c],
+          })
+          .find_map(|c| match *c {
+            Content::Control(Control::Choice { ref choice_labels, .. }) => Some(choice_labels.clone()),
+            _ => None,
+          });
+        if let (Some(next), Some(choice_labels)) = (next, choice_labels) {
+          choices.push((next, choice_labels));
+        }
+      }
+    }
+
+    for (next, choice_labels) in choices {
+      if let Some(&mut Node::Branch { ref mut branches, .. }) = self.nodes.get_mut(next as usize) {
+        if branches.len() != choice_labels.len() {
+          continue;
+        }
+        for (branch, choice) in branches.iter_mut().zip(choice_labels) {
+          branch.choice = Some(choice);
+        }
+      }
+    }
+  }
+
+  /// Set the message of every message node that has a label to the index of that label's string
+  /// in `msyt`, the MSYT of the paired MSBT.
+  pub fn apply_labels(&mut self, msyt: &Msyt) -> Result<()> {
+    for (i, node) in self.nodes.iter_mut().enumerate() {
+      if let Node::Message { ref mut message, label: Some(ref label), .. } = *node {
+        let index = match msyt.entries.get_full(label) {
+          Some((pos, _, entry)) => entry.index.unwrap_or(pos as u32),
+          None => failure::bail!("message node {} has label {}, which is not in the paired msyt", i, label),
+        };
+        if index > u32::from(u16::MAX) {
+          failure::bail!("message node {} has label {}, whose index {} is too large", i, label, index);
+        }
+        *message = index as u16;
+      }
+    }
+    Ok(())
+  }
+
+  /// Serialise this MSBF, writing its sections in the recorded order.
+  pub fn into_bytes(self) -> Result<Vec<u8>> {
+    let endianness = self.platform.endianness();
+
+    let mut header = [0; raw::HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[0x08..0x0A].copy_from_slice(match endianness {
+      Endianness::Big => &[0xFE, 0xFF],
+      Endianness::Little => &[0xFF, 0xFE],
+    });
+    header[0x0C] = match self.encoding {
+      TextEncoding::Utf8 => 0,
+      TextEncoding::Utf16 => 1,
+    };
+    header[0x0D] = 3;
+
+    let flows: Vec<(String, u32)> = self.flows.into_iter().collect();
+    let mut raw = RawMsbt {
+      header,
+      sections: vec![
+        RawSection {
+          magic: *b"FLW2",
+          data: write_nodes(&self.nodes, endianness).with_context(|_| "could not write flw2")?,
+        },
+        RawSection {
+          magic: *b"FEN1",
+          data: raw::write_labels(&flows, self.group_count, endianness).with_context(|_| "could not write fen1")?,
+        },
+      ],
+    };
+    for (magic, data) in self.unknown_sections {
+      raw.sections.push(RawSection {
+        magic: crate::model::section_magic(&magic)?,
+        data: data.0,
+      });
+    }
+    if let Some(header) = self.header {
+      header.apply(&mut raw)?;
+    }
+    raw.to_bytes()
+  }
+}
+
+fn read_nodes(data: &[u8], endianness: Endianness) -> Result<Vec<Node>> {
+  let read_u16 = |pos: usize| -> Result<u16> {
+    let bytes = data.get(pos..pos + 2).ok_or_else(|| failure::format_err!("flw2 too short"))?;
+    Ok(endianness.read_u16(bytes)?)
+  };
+  let next = |n: u16| if n == NO_NODE { None } else { Some(n) };
+
+  let node_count = read_u16(0)? as usize;
+  let branch_count = read_u16(2)? as usize;
+  let branch_start = 8 + node_count * NODE_LEN;
+  let branch_targets = (0..branch_count)
+    .map(|i| read_u16(branch_start + i * 2))
+    .collect::<Result<Vec<_>>>()?;
+
+  (0..node_count)
+    .map(|i| {
+      let pos = 8 + i * NODE_LEN;
+      let kind = read_u16(pos)?;
+      let mut f = [0; 7];
+      for (j, field) in f.iter_mut().enumerate() {
+        *field = read_u16(pos + 2 + j * 2)?;
+      }
+
+      let node = match kind {
+        1 => Node::Message {
+          field_1: f[0],
+          group: f[1],
+          message: f[2],
+          label: None,
+          next: next(f[3]),
+          field_5: f[4],
+          field_6: f[5],
+          field_7: f[6],
+        },
+        2 => {
+          let (count, start) = (f[4] as usize, f[5] as usize);
+          let branches = branch_targets.get(start..start + count)
+            .ok_or_else(|| failure::format_err!("branches of node {} out of range", i))?
+            .iter()
+            .map(|&n| Branch {
+              next: next(n),
+              choice: None,
+            })
+            .collect();
+          Node::Branch {
+            field_1: f[0],
+            field_2: f[1],
+            condition: f[2],
+            field_4: f[3],
+            field_7: f[6],
+            branches,
+          }
+        },
+        3 => Node::Event {
+          field_1: f[0],
+          field_2: f[1],
+          event: f[2],
+          next: next(f[3]),
+          field_5: f[4],
+          field_6: f[5],
+          field_7: f[6],
+        },
+        4 => Node::Entry {
+          field_1: f[0],
+          field_2: f[1],
+          field_3: f[2],
+          next: next(f[3]),
+          field_5: f[4],
+          field_6: f[5],
+          field_7: f[6],
+        },
+        _ => Node::Unknown {
+          node_kind: kind,
+          fields: f,
+        },
+      };
+      Ok(node)
+    })
+    .collect()
+}
+
+/// Write the nodes followed by their branch targets, which are laid out in node order.
+fn write_nodes(nodes: &[Node], endianness: Endianness) -> Result<Vec<u8>> {
+  let next = |n: Option<u16>| n.unwrap_or(NO_NODE);
+
+  let mut branch_targets = Vec::new();
+  let mut buf = Vec::with_capacity(8 + nodes.len() * NODE_LEN);
+  endianness.write_u16(&mut buf, nodes.len() as u16)?;
+  let branch_count = nodes.iter()
+    .map(|n| match *n {
+      Node::Branch { ref branches, .. } => branches.len(),
+      _ => 0,
+    })
+    .sum::<usize>();
+  endianness.write_u16(&mut buf, branch_count as u16)?;
+  endianness.write_u32(&mut buf, 0)?;
+
+  for node in nodes {
+    let (kind, fields) = match *node {
+      Node::Message { field_1, group, message, next: n, field_5, field_6, field_7, .. } =>
+        (1, [field_1, group, message, next(n), field_5, field_6, field_7]),
+      Node::Branch { field_1, field_2, condition, field_4, field_7, ref branches } => {
+        let start = branch_targets.len() as u16;
+        branch_targets.extend(branches.iter().map(|b| next(b.next)));
+        (2, [field_1, field_2, condition, field_4, branches.len() as u16, start, field_7])
+      },
+      Node::Event { field_1, field_2, event, next: n, field_5, field_6, field_7 } =>
+        (3, [field_1, field_2, event, next(n), field_5, field_6, field_7]),
+      Node::Entry { field_1, field_2, field_3, next: n, field_5, field_6, field_7 } =>
+        (4, [field_1, field_2, field_3, next(n), field_5, field_6, field_7]),
+      Node::Unknown { node_kind, fields } => (node_kind, fields),
+    };
+    endianness.write_u16(&mut buf, kind)?;
+    for field in &fields {
+      endianness.write_u16(&mut buf, *field)?;
+    }
+  }
+  for target in branch_targets {
+    endianness.write_u16(&mut buf, target)?;
+  }
+
+  Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// An entry node, a message with two choices and a branch on them.
+  fn msbf() -> Msbf {
+    let mut flows = IndexMap::new();
+    flows.insert("Talk".to_string(), 0);
+    Msbf {
+      platform: Platform::Switch,
+      encoding: TextEncoding::Utf16,
+      header: None,
+      group_count: 1,
+      flows,
+      nodes: vec![
+        Node::Entry { field_1: 0, field_2: 0, field_3: 0, next: Some(1), field_5: 0, field_6: 0, field_7: 0 },
+        Node::Message { field_1: 0, group: 0, message: 1, label: None, next: Some(2), field_5: 0, field_6: 0, field_7: 0 },
+        Node::Branch {
+          field_1: 0,
+          field_2: 0,
+          condition: 0,
+          field_4: 0,
+          field_7: 0,
+          branches: vec![Branch { next: None, choice: None }, Branch { next: Some(1), choice: None }],
+        },
+      ],
+      unknown_sections: IndexMap::new(),
+    }
+  }
+
+  fn msyt(choices: &str) -> Msyt {
+    let yaml = format!(
+      "group_count: 1\nentries:\n  Hello:\n    contents:\n      - text: hi\n  Ask:\n    contents:\n      \
+       - control:\n          kind: choice\n          choice_labels: {}\n          selected_index: 0\n          \
+       cancel_index: 1\n          unknown: 0\n",
+      choices,
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+  }
+
+  #[test]
+  fn nodes_round_trip() {
+    let flw2: &[u8] = &[
+      2, 0, 2, 0, 0, 0, 0, 0,
+      1, 0, 0, 0, 0, 0, 5, 0, 1, 0, 0, 0, 0, 0, 0, 0,
+      2, 0, 0, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 0, 0,
+      0xFF, 0xFF, 0, 0,
+    ];
+    let nodes = read_nodes(flw2, Endianness::Little).unwrap();
+    match nodes[1] {
+      Node::Branch { condition, ref branches, .. } => {
+        assert_eq!(condition, 3);
+        assert_eq!(branches, &[Branch { next: None, choice: None }, Branch { next: Some(0), choice: None }]);
+      },
+      ref n => panic!("expected a branch, got {:?}", n),
+    }
+    assert_eq!(write_nodes(&nodes, Endianness::Little).unwrap(), flw2);
+  }
+
+  #[test]
+  fn msbf_round_trips() {
+    let bytes = msbf().into_bytes().unwrap();
+    let mut parsed = Msbf::from_bytes(&bytes, None).unwrap();
+    assert!(parsed.header.is_some());
+    parsed.header = None;
+    assert_eq!(parsed, msbf());
+  }
+
+  #[test]
+  fn paired_msyt_names_messages_and_choices() {
+    let bytes = msbf().into_bytes().unwrap();
+    let parsed = Msbf::from_bytes(&bytes, Some(&msyt("[4, 5]"))).unwrap();
+    match parsed.nodes[1] {
+      Node::Message { ref label, .. } => assert_eq!(label.as_ref().map(String::as_str), Some("Ask")),
+      ref n => panic!("expected a message, got {:?}", n),
+    }
+    match parsed.nodes[2] {
+      Node::Branch { ref branches, .. } => assert_eq!(branches.iter().map(|b| b.choice).collect::<Vec<_>>(), [Some(4), Some(5)]),
+      ref n => panic!("expected a branch, got {:?}", n),
+    }
+  }
+
+  #[test]
+  fn mismatched_choice_count_records_no_choices() {
+    let bytes = msbf().into_bytes().unwrap();
+    let parsed = Msbf::from_bytes(&bytes, Some(&msyt("[4, 5, 6]"))).unwrap();
+    match parsed.nodes[1] {
+      Node::Message { ref label, .. } => assert_eq!(label.as_ref().map(String::as_str), Some("Ask")),
+      ref n => panic!("expected a message, got {:?}", n),
+    }
+    match parsed.nodes[2] {
+      Node::Branch { ref branches, .. } => assert!(branches.iter().all(|b| b.choice.is_none())),
+      ref n => panic!("expected a branch, got {:?}", n),
+    }
+  }
+
+  #[test]
+  fn labels_choose_messages() {
+    let mut msbf = msbf();
+    if let Node::Message { ref mut label, .. } = msbf.nodes[1] {
+      *label = Some("Hello".to_string());
+    }
+    msbf.apply_labels(&msyt("[4, 5]")).unwrap();
+    match msbf.nodes[1] {
+      Node::Message { message, .. } => assert_eq!(message, 0),
+      ref n => panic!("expected a message, got {:?}", n),
+    }
+
+    if let Node::Message { ref mut label, .. } = msbf.nodes[1] {
+      *label = Some("Missing".to_string());
+    }
+    assert!(msbf.apply_labels(&msyt("[4, 5]")).is_err());
+  }
+}
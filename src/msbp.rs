@@ -0,0 +1,647 @@
+//! MSBP project files, which describe the control sequences and attributes of the MSBTs made with
+//! them.
+//!
+//! An MSBP has the same header and section framing as an MSBT. The sections read here are:
+//!
+//! - ATI2, ALB1 and ALI2: the type and offset of each attribute, their labels and the items of
+//!   list attributes.
+//! - TGG2, TAG2, TGP2 and TGL2: tag groups, the tags in each group, the parameters of each tag and
+//!   the items of list parameters.
+//!
+//! Control sequences decoded with an MSBP become [`Control::Tag`], which records the type of each
+//! parameter so that it can be written again without the MSBP.
+//!
+//! The colour table (CLR1 and CLB1) is not read, since nothing in an MSBT refers to colours by the
+//! names it gives them.
+
+use crate::{
+  Result,
+  botw::Control,
+  game::{ControlParser, Profile, code_unit_len, encode_text, parse_text},
+  raw::RawMsbt,
+};
+
+use byteordered::{Endian, Endianness};
+use failure::ResultExt;
+use indexmap::IndexMap;
+use msbt::Header;
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+  collections::HashMap,
+  io::{Cursor, Read, Write},
+};
+
+const MAGIC: &[u8] = b"MsgPrjBn";
+
+/// A parsed MSBP.
+#[derive(Debug, Default)]
+pub struct Msbp {
+  /// The attributes of each string, in the order they are defined.
+  pub attributes: Vec<AttributeInfo>,
+  pub tag_groups: Vec<TagGroup>,
+}
+
+#[derive(Debug)]
+pub struct AttributeInfo {
+  pub name: String,
+  pub kind: ParamType,
+  /// The offset of this attribute in each ATR1 record.
+  pub offset: u32,
+  /// The items of a list attribute.
+  pub list: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct TagGroup {
+  /// The control type used for the tags in this group.
+  pub id: u16,
+  pub name: String,
+  /// The tags in this group, indexed by their control subtype.
+  pub tags: Vec<TagInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagInfo {
+  pub name: String,
+  pub params: Vec<TagParam>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagParam {
+  pub name: String,
+  pub kind: ParamType,
+  /// The items of a list parameter.
+  pub list: Vec<String>,
+}
+
+/// The type of a tag parameter or attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+  U8,
+  U16,
+  U32,
+  I8,
+  I16,
+  I32,
+  F32,
+  String,
+  List,
+  /// A type msyt does not know. Tags and attributes with parameters of this type are not
+  /// decoded.
+  Unknown(u8),
+}
+
+impl ParamType {
+  pub fn from_u8(u: u8) -> Self {
+    match u {
+      0 => ParamType::U8,
+      1 => ParamType::U16,
+      2 => ParamType::U32,
+      3 => ParamType::I8,
+      4 => ParamType::I16,
+      5 => ParamType::I32,
+      6 => ParamType::F32,
+      8 => ParamType::String,
+      9 => ParamType::List,
+      x => ParamType::Unknown(x),
+    }
+  }
+}
+
+/// The value of a tag parameter or attribute, tagged with its type.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Value {
+  U8(u8),
+  U16(u16),
+  U32(u32),
+  I8(i8),
+  I16(i16),
+  I32(i32),
+  F32(f32),
+  String(String),
+  /// An item of a list. Only `index` is written; `item` is its name in the MSBP, for reference.
+  List { index: u8, item: String },
+}
+
+/// The value of an attribute and where it is in the ATR1 record.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Attribute {
+  pub offset: u32,
+  pub value: Value,
+}
+
+impl Msbp {
+  pub fn parse(bytes: &[u8]) -> Result<Self> {
+    if !bytes.starts_with(MAGIC) {
+      failure::bail!("invalid msbp: bad magic");
+    }
+    let raw = RawMsbt::parse(bytes).with_context(|_| "could not read msbp sections")?;
+    let reader = |magic: &[u8; 4]| raw.section(magic).map(|s| SectionReader {
+      data: &s.data,
+      endianness: raw.endianness(),
+    });
+
+    let mut msbp = Msbp::default();
+
+    if let (Some(ati2), Some(alb1)) = (reader(b"ATI2"), reader(b"ALB1")) {
+      let names = alb1.labels().with_context(|_| "could not read alb1")?;
+      let lists = match reader(b"ALI2") {
+        Some(ali2) => ali2.attribute_lists().with_context(|_| "could not read ali2")?,
+        None => Vec::new(),
+      };
+      let count = ati2.u32(0)? as usize;
+      for i in 0..count {
+        let pos = 4 + i * 8;
+        let kind = ParamType::from_u8(ati2.u8(pos)?);
+        let list_index = ati2.u16(pos + 2)? as usize;
+        let list = match kind {
+          ParamType::List => lists.get(list_index).cloned()
+            .ok_or_else(|| failure::format_err!("invalid msbp: missing attribute list {}", list_index))?,
+          _ => Vec::new(),
+        };
+        msbp.attributes.push(AttributeInfo {
+          name: names.get(&i).cloned().unwrap_or_else(|| format!("#{}", i)),
+          kind,
+          offset: ati2.u32(pos + 4)?,
+          list,
+        });
+      }
+    }
+
+    if let (Some(tgg2), Some(tag2), Some(tgp2)) = (reader(b"TGG2"), reader(b"TAG2"), reader(b"TGP2")) {
+      let list_items = match reader(b"TGL2") {
+        Some(tgl2) => tgl2.indexed(|pos| Ok(tgl2.c_str(pos)?.0)).with_context(|_| "could not read tgl2")?,
+        None => Vec::new(),
+      };
+      let params = tgp2.indexed(|pos| {
+        let kind = ParamType::from_u8(tgp2.u8(pos)?);
+        let (list, name_pos) = match kind {
+          ParamType::List => {
+            let count = tgp2.u16(pos + 2)? as usize;
+            let list = (0..count)
+              .map(|i| {
+                let index = tgp2.u16(pos + 4 + i * 2)? as usize;
+                list_items.get(index).cloned()
+                  .ok_or_else(|| failure::format_err!("invalid msbp: missing list item {}", index))
+              })
+              .collect::<Result<_>>()?;
+            (list, pos + 4 + count * 2)
+          },
+          _ => (Vec::new(), pos + 1),
+        };
+        Ok(TagParam {
+          name: tgp2.c_str(name_pos)?.0,
+          kind,
+          list,
+        })
+      }).with_context(|_| "could not read tgp2")?;
+      let tags = tag2.indexed(|pos| {
+        let (indices, name_pos) = tag2.u16_list(pos)?;
+        let params = indices.into_iter()
+          .map(|i| params.get(i as usize).cloned()
+            .ok_or_else(|| failure::format_err!("invalid msbp: missing tag parameter {}", i)))
+          .collect::<Result<_>>()?;
+        Ok(TagInfo {
+          name: tag2.c_str(name_pos)?.0,
+          params,
+        })
+      }).with_context(|_| "could not read tag2")?;
+      msbp.tag_groups = tgg2.indexed(|pos| {
+        let id = tgg2.u16(pos)?;
+        let (indices, name_pos) = tgg2.u16_list(pos + 2)?;
+        let tags = indices.into_iter()
+          .map(|i| tags.get(i as usize).cloned()
+            .ok_or_else(|| failure::format_err!("invalid msbp: missing tag {}", i)))
+          .collect::<Result<_>>()?;
+        Ok(TagGroup {
+          id,
+          name: tgg2.c_str(name_pos)?.0,
+          tags,
+        })
+      }).with_context(|_| "could not read tgg2")?;
+    }
+
+    Ok(msbp)
+  }
+
+  fn decode_tag(&self, header: &Header, kind: u16, sub_kind: u16, data: &[u8]) -> Option<Control> {
+    let (group, tag) = self.tag(kind, sub_kind)?;
+
+    let mut c = Cursor::new(data);
+    let mut params = IndexMap::with_capacity(tag.params.len());
+    for param in &tag.params {
+      let value = read_tag_value(header, param, &mut c).ok()?;
+      if params.insert(param.name.clone(), value).is_some() {
+        // parameters with the same name could not be told apart when writing
+        return None;
+      }
+    }
+    let padding = data[c.position() as usize..].to_vec();
+
+    Some(Control::Tag {
+      group: kind,
+      tag: sub_kind,
+      name: format!("{}.{}", group.name, tag.name),
+      params,
+      padding,
+    })
+  }
+
+  fn tag(&self, group: u16, tag: u16) -> Option<(&TagGroup, &TagInfo)> {
+    let group = self.tag_groups.iter().find(|g| g.id == group)?;
+    group.tags.get(tag as usize).map(|t| (group, t))
+  }
+
+  /// Decode the ATR1 section in `data` into the attributes of each string.
+  ///
+  /// Returns `None` if the MSBP defines no attributes or any of them has a type msyt does not know.
+  pub(crate) fn decode_attributes(&self, header: &Header, data: &[u8]) -> Result<Option<Vec<IndexMap<String, Attribute>>>> {
+    if self.attributes.is_empty() || self.attributes.iter().any(|a| matches!(a.kind, ParamType::Unknown(_))) {
+      return Ok(None);
+    }
+
+    let atr1 = SectionReader {
+      data,
+      endianness: header.endianness(),
+    };
+    let count = atr1.u32(0)? as usize;
+    let size = atr1.u32(4)? as usize;
+    let records_end = count.checked_mul(size)
+      .and_then(|len| len.checked_add(8))
+      .ok_or_else(|| failure::format_err!("invalid msbt: atr1 declares {} records of {} bytes", count, size))?;
+    // some ATR1 sections declare their records but do not contain them
+    if atr1.data.len() < records_end {
+      return Ok(None);
+    }
+    (0..count)
+      .map(|i| {
+        let record = 8 + i * size;
+        self.attributes.iter()
+          .map(|info| {
+            let pos = record + info.offset as usize;
+            let value = match info.kind {
+              ParamType::String => {
+                let offset = atr1.u32(pos)? as usize;
+                let bytes = atr1.data.get(offset..)
+                  .ok_or_else(|| failure::format_err!("invalid msbt: attribute string past end of atr1"))?;
+                Value::String(read_nul_terminated(header, bytes)?)
+              },
+              kind => {
+                let bytes = atr1.data.get(pos..)
+                  .ok_or_else(|| failure::format_err!("invalid msbt: attribute {} past end of atr1", info.name))?;
+                read_value(header, kind, &info.list, &mut Cursor::new(bytes))?
+              },
+            };
+            Ok((info.name.clone(), Attribute {
+              offset: info.offset,
+              value,
+            }))
+          })
+          .collect::<Result<IndexMap<_, _>>>()
+          .with_context(|_| format!("could not read attributes for string {}", i))
+          .map_err(Into::into)
+      })
+      .collect::<Result<Vec<_>>>()
+      .map(Some)
+  }
+}
+
+impl Profile for Msbp {
+  fn controls(&self) -> &[(u16, ControlParser)] {
+    &[]
+  }
+
+  /// Parse a control sequence of type `kind` as a tag. `buf` starts after the control type.
+  ///
+  /// Tags that are not in the MSBP, or whose parameters do not match it, are kept as
+  /// [`Control::Unknown`].
+  fn parse_control(&self, header: &Header, kind: u16, buf: &[u8]) -> Result<(usize, Control)> {
+    let (read, unknown) = Control::parse_unknown(header, kind, buf)?;
+    let tag = match unknown {
+      Control::Unknown { sub_kind, ref data, .. } => self.decode_tag(header, kind, sub_kind, data),
+      _ => unreachable!("parse_unknown only returns unknown controls"),
+    };
+    Ok((read, tag.unwrap_or(unknown)))
+  }
+}
+
+/// Write tag parameters in the order given.
+pub(crate) fn write_tag_values<'a>(header: &Header, values: impl Iterator<Item = &'a Value>, writer: &mut dyn Write) -> Result<()> {
+  for value in values {
+    match *value {
+      Value::String(ref s) => {
+        let bytes = encode_text(header, s);
+        header.endianness().write_u16(&mut *writer, bytes.len() as u16)?;
+        writer.write_all(&bytes)?;
+      },
+      ref v => write_value(header, v, writer)?,
+    }
+  }
+  Ok(())
+}
+
+/// Encode an ATR1 section with records of `size` bytes holding `attributes`.
+pub(crate) fn encode_attributes(header: &Header, size: u32, attributes: &[Option<&IndexMap<String, Attribute>>]) -> Result<Vec<u8>> {
+  let endianness = header.endianness();
+  let records_end = 8 + attributes.len() * size as usize;
+  let mut buf = Vec::with_capacity(records_end);
+  endianness.write_u32(&mut buf, attributes.len() as u32)?;
+  endianness.write_u32(&mut buf, size)?;
+  buf.resize(records_end, 0);
+
+  let mut strings = Vec::new();
+  for (i, fields) in attributes.iter().enumerate() {
+    let record = 8 + i * size as usize;
+    for (name, attribute) in fields.iter().flat_map(|f| f.iter()) {
+      let mut value = Vec::new();
+      match attribute.value {
+        Value::String(ref s) => {
+          endianness.write_u32(&mut value, (records_end + strings.len()) as u32)?;
+          strings.extend(encode_text(header, s));
+          crate::game::write_code_unit(header, &mut strings, 0)?;
+        },
+        ref v => write_value(header, v, &mut value)?,
+      }
+      let start = record + attribute.offset as usize;
+      match buf.get_mut(start..start + value.len()) {
+        Some(dest) if start + value.len() <= record + size as usize => dest.copy_from_slice(&value),
+        _ => failure::bail!("attribute {} of string {} does not fit in a record of {} bytes", name, i, size),
+      }
+    }
+  }
+  buf.append(&mut strings);
+
+  Ok(buf)
+}
+
+fn read_tag_value(header: &Header, param: &TagParam, c: &mut Cursor<&[u8]>) -> Result<Value> {
+  match param.kind {
+    ParamType::String => {
+      let len = header.endianness().read_u16(&mut *c)?;
+      let mut bytes = vec![0; len as usize];
+      c.read_exact(&mut bytes)?;
+      Ok(Value::String(parse_text(header, &bytes)?))
+    },
+    kind => read_value(header, kind, &param.list, c),
+  }
+}
+
+/// Read a value that is not a string.
+fn read_value(header: &Header, kind: ParamType, list: &[String], c: &mut Cursor<&[u8]>) -> Result<Value> {
+  let endianness = header.endianness();
+  let value = match kind {
+    ParamType::U8 => Value::U8(read_u8(c)?),
+    ParamType::U16 => Value::U16(endianness.read_u16(&mut *c)?),
+    ParamType::U32 => Value::U32(endianness.read_u32(&mut *c)?),
+    ParamType::I8 => Value::I8(read_u8(c)? as i8),
+    ParamType::I16 => Value::I16(endianness.read_i16(&mut *c)?),
+    ParamType::I32 => Value::I32(endianness.read_i32(&mut *c)?),
+    ParamType::F32 => Value::F32(endianness.read_f32(&mut *c)?),
+    ParamType::List => {
+      let index = read_u8(c)?;
+      let item = list.get(index as usize)
+        .cloned()
+        .ok_or_else(|| failure::format_err!("list item {} out of range", index))?;
+      Value::List { index, item }
+    },
+    ParamType::String | ParamType::Unknown(_) => failure::bail!("cannot read parameter of type {:?}", kind),
+  };
+  Ok(value)
+}
+
+/// Write a value that is not a string.
+fn write_value(header: &Header, value: &Value, mut writer: &mut dyn Write) -> Result<()> {
+  let endianness = header.endianness();
+  match *value {
+    Value::U8(x) => writer.write_all(&[x])?,
+    Value::U16(x) => endianness.write_u16(&mut writer, x)?,
+    Value::U32(x) => endianness.write_u32(&mut writer, x)?,
+    Value::I8(x) => writer.write_all(&[x as u8])?,
+    Value::I16(x) => endianness.write_i16(&mut writer, x)?,
+    Value::I32(x) => endianness.write_i32(&mut writer, x)?,
+    Value::F32(x) => endianness.write_f32(&mut writer, x)?,
+    Value::List { index, .. } => writer.write_all(&[index])?,
+    Value::String(_) => failure::bail!("strings are written by their caller"),
+  }
+  Ok(())
+}
+
+fn read_u8(c: &mut Cursor<&[u8]>) -> Result<u8> {
+  let mut buf = [0; 1];
+  c.read_exact(&mut buf)?;
+  Ok(buf[0])
+}
+
+fn read_nul_terminated(header: &Header, bytes: &[u8]) -> Result<String> {
+  let unit = code_unit_len(header);
+  let len = bytes.chunks(unit)
+    .position(|u| u.iter().all(|&b| b == 0))
+    .ok_or_else(|| failure::format_err!("unterminated string"))?;
+  parse_text(header, &bytes[..len * unit])
+}
+
+/// Reads values at offsets within the data of an MSBP section.
+struct SectionReader<'a> {
+  data: &'a [u8],
+  endianness: Endianness,
+}
+
+impl<'a> SectionReader<'a> {
+  fn bytes(&self, pos: usize, len: usize) -> Result<&'a [u8]> {
+    self.data.get(pos..pos + len)
+      .ok_or_else(|| failure::format_err!("offset {:#x} out of range", pos))
+  }
+
+  fn u8(&self, pos: usize) -> Result<u8> {
+    Ok(self.bytes(pos, 1)?[0])
+  }
+
+  fn u16(&self, pos: usize) -> Result<u16> {
+    self.endianness.read_u16(self.bytes(pos, 2)?).map_err(Into::into)
+  }
+
+  fn u32(&self, pos: usize) -> Result<u32> {
+    self.endianness.read_u32(self.bytes(pos, 4)?).map_err(Into::into)
+  }
+
+  /// Read a NUL-terminated ASCII string, returning it and the offset after its terminator.
+  fn c_str(&self, pos: usize) -> Result<(String, usize)> {
+    let rest = self.data.get(pos..).ok_or_else(|| failure::format_err!("offset {:#x} out of range", pos))?;
+    let len = rest.iter()
+      .position(|&b| b == 0)
+      .ok_or_else(|| failure::format_err!("unterminated string at {:#x}", pos))?;
+    Ok((String::from_utf8_lossy(&rest[..len]).into_owned(), pos + len + 1))
+  }
+
+  /// Read a u16 count followed by that many u16s, returning them and the offset after them.
+  fn u16_list(&self, pos: usize) -> Result<(Vec<u16>, usize)> {
+    let count = self.u16(pos)? as usize;
+    let list = (0..count)
+      .map(|i| self.u16(pos + 2 + i * 2))
+      .collect::<Result<_>>()?;
+    Ok((list, pos + 2 + count * 2))
+  }
+
+  /// Read a table of entries: a u16 count, two bytes of padding and a u32 offset for each entry.
+  fn indexed<T>(&self, mut read: impl FnMut(usize) -> Result<T>) -> Result<Vec<T>> {
+    let count = self.u16(0)? as usize;
+    (0..count)
+      .map(|i| {
+        let offset = self.u32(4 + i * 4)? as usize;
+        read(offset).with_context(|_| format!("could not read entry {}", i)).map_err(Into::into)
+      })
+      .collect()
+  }
+
+  /// Read a hash table of labels, like LBL1, into each label's index mapped to its name.
+  fn labels(&self) -> Result<HashMap<usize, String>> {
+    let labels = crate::raw::read_labels(self.data, self.endianness)?;
+    Ok(labels.into_iter().map(|(name, index)| (index as usize, name)).collect())
+  }
+
+  /// Read ALI2: a u32 count and a u32 offset for each list, where each list is a u32 count and a
+  /// u32 offset for each item relative to the start of the list.
+  fn attribute_lists(&self) -> Result<Vec<Vec<String>>> {
+    let count = self.u32(0)? as usize;
+    (0..count)
+      .map(|i| {
+        let list = self.u32(4 + i * 4)? as usize;
+        let items = self.u32(list)? as usize;
+        (0..items)
+          .map(|j| Ok(self.c_str(list + self.u32(list + 4 + j * 4)? as usize)?.0))
+          .collect()
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::raw::{HEADER_LEN, RawSection};
+
+  use msbt::{Encoding, builder::MsbtBuilder};
+
+  /// An MSBP with one tag group, `System`, holding a `Size` tag with a u16 parameter and a `Font`
+  /// tag with a list parameter, and two attributes: a u8 and a string.
+  fn msbp_bytes() -> Vec<u8> {
+    let e = Endianness::Little;
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(MAGIC);
+    header[0x08..0x0A].copy_from_slice(&[0xFF, 0xFE]);
+
+    let section = |magic: &[u8; 4], data: Vec<u8>| RawSection { magic: *magic, data };
+    let u16s = |values: &[u16]| values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect::<Vec<_>>();
+
+    let mut ati2 = vec![2, 0, 0, 0];
+    ati2.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    ati2.extend_from_slice(&[8, 0, 0, 0, 4, 0, 0, 0]);
+    let labels = vec![("kind".to_string(), 0), ("speaker".to_string(), 1)];
+    let alb1 = crate::raw::write_labels(&labels, 1, e).unwrap();
+
+    let mut size = u16s(&[1]);
+    size.extend_from_slice(b"size\0");
+    let mut font = vec![9, 0];
+    font.extend(u16s(&[2, 0, 1]));
+    font.extend_from_slice(b"face\0");
+
+    let sections = vec![
+      section(b"ATI2", ati2),
+      section(b"ALB1", alb1),
+      section(b"TGG2", table(&[[u16s(&[0, 2, 0, 1]), b"System\0".to_vec()].concat()])),
+      section(b"TAG2", table(&[
+        [u16s(&[1, 0]), b"Size\0".to_vec()].concat(),
+        [u16s(&[1, 1]), b"Font\0".to_vec()].concat(),
+      ])),
+      section(b"TGP2", table(&[size, font])),
+      section(b"TGL2", table(&[b"Normal\0".to_vec(), b"Hylian\0".to_vec()])),
+    ];
+    RawMsbt { header, sections }.to_bytes().unwrap()
+  }
+
+  /// An MSBP table: a u16 count, padding, then the offset of each entry.
+  fn table(entries: &[Vec<u8>]) -> Vec<u8> {
+    let mut buf = (entries.len() as u16).to_le_bytes().to_vec();
+    buf.extend_from_slice(&[0, 0]);
+    let mut offset = 4 + entries.len() * 4;
+    for entry in entries {
+      buf.extend_from_slice(&(offset as u32).to_le_bytes());
+      offset += entry.len();
+    }
+    for entry in entries {
+      buf.extend_from_slice(entry);
+    }
+    buf
+  }
+
+  #[test]
+  fn parses_tags_and_attributes() {
+    let msbp = Msbp::parse(&msbp_bytes()).unwrap();
+
+    assert_eq!(msbp.tag_groups.len(), 1);
+    let group = &msbp.tag_groups[0];
+    assert_eq!((group.id, group.name.as_str()), (0, "System"));
+    let tags: Vec<_> = group.tags.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(tags, ["Size", "Font"]);
+    assert_eq!(group.tags[0].params[0].kind, ParamType::U16);
+    assert_eq!(group.tags[1].params[0].list, ["Normal", "Hylian"]);
+
+    let attributes: Vec<_> = msbp.attributes.iter().map(|a| (a.name.as_str(), a.kind, a.offset)).collect();
+    assert_eq!(attributes, [("kind", ParamType::U8, 0), ("speaker", ParamType::String, 4)]);
+  }
+
+  #[test]
+  fn tags_round_trip() {
+    let msbp = Msbp::parse(&msbp_bytes()).unwrap();
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let header = builder.header();
+
+    // Font with item 1, then a byte of padding
+    let control = [0, 0, 1, 0, 2, 0, 1, 0];
+    let (read, tag) = msbp.parse_control(header, 0, &control[2..]).unwrap();
+    assert_eq!(read, control.len() - 2);
+    match tag {
+      Control::Tag { ref name, ref params, ref padding, .. } => {
+        assert_eq!(name, "System.Font");
+        assert_eq!(params["face"], Value::List { index: 1, item: "Hylian".into() });
+        assert_eq!(padding, &[0]);
+      },
+      ref c => panic!("expected a tag, got {:?}", c),
+    }
+
+    let mut written = Vec::new();
+    tag.write(header, &mut written).unwrap();
+    assert_eq!(&written[2..], &control[..]);
+  }
+
+  #[test]
+  fn attributes_round_trip() {
+    let msbp = Msbp::parse(&msbp_bytes()).unwrap();
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+    let header = builder.header();
+
+    let mut fields = IndexMap::new();
+    fields.insert("kind".to_string(), Attribute { offset: 0, value: Value::U8(3) });
+    fields.insert("speaker".to_string(), Attribute { offset: 4, value: Value::String("Link".into()) });
+    let atr1 = encode_attributes(header, 8, &[Some(&fields)]).unwrap();
+
+    let decoded = msbp.decode_attributes(header, &atr1).unwrap().unwrap();
+    assert_eq!(decoded, [fields]);
+  }
+
+  #[test]
+  fn oversized_atr1_is_not_decoded() {
+    let msbp = Msbp::parse(&msbp_bytes()).unwrap();
+    let builder = MsbtBuilder::new(Endianness::Little, Encoding::Utf16, Some(1));
+
+    // the size of these records overflows on 32-bit targets and is past the end of ATR1 elsewhere
+    let atr1 = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+    match msbp.decode_attributes(builder.header(), &atr1) {
+      Ok(None) | Err(_) => {},
+      Ok(Some(fields)) => panic!("decoded {} records", fields.len()),
+    }
+  }
+}
@@ -0,0 +1,334 @@
+//! A minimal view of the section layout of an MSBT, used to keep parts of the file that the msbt
+//! crate does not preserve when rebuilding it.
+
+use crate::Result;
+
+use byteordered::{Endian, Endianness};
+
+use failure::ResultExt;
+
+use std::borrow::Cow;
+
+pub const HEADER_LEN: usize = 0x20;
+const SECTION_HEADER_LEN: usize = 0x10;
+const SECTION_ALIGNMENT: usize = 0x10;
+const PADDING_BYTE: u8 = 0xAB;
+/// The parts of the header that are not the magic, byte order mark, encoding, version, section
+/// count or file size.
+const UNKNOWN_HEADER_RANGES: [(usize, usize); 3] = [(0x0A, 0x0C), (0x10, 0x12), (0x16, HEADER_LEN)];
+pub const UNKNOWN_HEADER_LEN: usize = 0x0E;
+/// The sections that the msbt crate reads.
+const KNOWN_SECTIONS: [[u8; 4]; 6] = [*b"LBL1", *b"ATR1", *b"ATO1", *b"TSY1", *b"NLI1", *b"TXT2"];
+
+#[derive(Debug)]
+pub struct RawMsbt {
+  pub header: [u8; HEADER_LEN],
+  pub sections: Vec<RawSection>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RawSection {
+  pub magic: [u8; 4],
+  pub data: Vec<u8>,
+}
+
+impl RawMsbt {
+  pub fn parse(bytes: &[u8]) -> Result<Self> {
+    if bytes.len() < HEADER_LEN {
+      failure::bail!("invalid msbt: file too short for header");
+    }
+
+    let mut header = [0; HEADER_LEN];
+    header.copy_from_slice(&bytes[..HEADER_LEN]);
+    let endianness = endianness(&header)?;
+    let section_count = endianness.read_u16(&header[0x0E..0x10]).with_context(|_| "could not read section count")?;
+
+    let mut sections = Vec::with_capacity(section_count as usize);
+    let mut pos = HEADER_LEN;
+    for i in 0..section_count {
+      let section_header = bytes.get(pos..pos + SECTION_HEADER_LEN)
+        .ok_or_else(|| failure::format_err!("invalid msbt: missing header for section {}", i))?;
+      let mut magic = [0; 4];
+      magic.copy_from_slice(&section_header[..4]);
+      let size = endianness.read_u32(&section_header[4..8]).with_context(|_| "could not read section size")? as usize;
+
+      let start = pos + SECTION_HEADER_LEN;
+      let data = bytes.get(start..start + size)
+        .ok_or_else(|| failure::format_err!("invalid msbt: section {} extends past end of file", String::from_utf8_lossy(&magic)))?;
+      sections.push(RawSection {
+        magic,
+        data: data.to_vec(),
+      });
+
+      pos = align(start + size);
+    }
+
+    Ok(RawMsbt {
+      header,
+      sections,
+    })
+  }
+
+  pub fn endianness(&self) -> Endianness {
+    endianness(&self.header).expect("header checked when parsed")
+  }
+
+  pub fn version(&self) -> u8 {
+    self.header[0x0D]
+  }
+
+  pub fn set_version(&mut self, version: u8) {
+    self.header[0x0D] = version;
+  }
+
+  /// The header bytes with no known meaning, concatenated.
+  pub fn unknown_header_bytes(&self) -> Vec<u8> {
+    UNKNOWN_HEADER_RANGES.iter()
+      .flat_map(|&(start, end)| self.header[start..end].iter().cloned())
+      .collect()
+  }
+
+  pub fn set_unknown_header_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+    if bytes.len() != UNKNOWN_HEADER_LEN {
+      failure::bail!("expected {} unknown header bytes but got {}", UNKNOWN_HEADER_LEN, bytes.len());
+    }
+    let mut pos = 0;
+    for &(start, end) in &UNKNOWN_HEADER_RANGES {
+      self.header[start..end].copy_from_slice(&bytes[pos..pos + end - start]);
+      pos += end - start;
+    }
+    Ok(())
+  }
+
+  /// The raw value of each string in TXT2, including its terminator.
+  pub fn txt2_strings(&self) -> Result<Vec<&[u8]>> {
+    let data = match self.section(b"TXT2") {
+      Some(s) => &s.data,
+      None => failure::bail!("invalid msbt: missing txt2"),
+    };
+    let endianness = self.endianness();
+    let read_u32 = |pos: usize| -> Result<usize> {
+      let bytes = data.get(pos..pos + 4).ok_or_else(|| failure::format_err!("invalid msbt: txt2 too short"))?;
+      Ok(endianness.read_u32(bytes).with_context(|_| "could not read txt2 offset")? as usize)
+    };
+
+    let count = read_u32(0)?;
+    let offsets = (0..count)
+      .map(|i| read_u32(4 + i * 4))
+      .collect::<Result<Vec<_>>>()?;
+    offsets.iter()
+      .enumerate()
+      .map(|(i, &start)| {
+        let end = offsets.get(i + 1).cloned().unwrap_or(data.len());
+        data.get(start..end)
+          .ok_or_else(|| failure::format_err!("invalid msbt: string {} extends past end of txt2", i))
+      })
+      .collect()
+  }
+
+  /// Remove and return the sections that the msbt crate does not read.
+  pub fn take_unknown_sections(&mut self) -> Vec<RawSection> {
+    let (known, unknown) = self.sections.drain(..).partition(|s| KNOWN_SECTIONS.contains(&s.magic));
+    self.sections = known;
+    unknown
+  }
+
+  pub fn section(&self, magic: &[u8]) -> Option<&RawSection> {
+    self.sections.iter().find(|s| s.magic == magic)
+  }
+
+  pub fn section_order(&self) -> Vec<[u8; 4]> {
+    self.sections.iter().map(|s| s.magic).collect()
+  }
+
+  /// Sort the sections to match `order`. Sections not in `order` are placed at the end.
+  pub fn reorder(&mut self, order: &[[u8; 4]]) {
+    self.sections.sort_by_key(|s| order.iter().position(|m| *m == s.magic).unwrap_or(order.len()));
+  }
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    let endianness = self.endianness();
+
+    let mut buf = self.header.to_vec();
+    for section in &self.sections {
+      buf.extend_from_slice(&section.magic);
+      endianness.write_u32(&mut buf, section.data.len() as u32).with_context(|_| "could not write section size")?;
+      buf.extend_from_slice(&[0; 8]);
+      buf.extend_from_slice(&section.data);
+      let len = align(buf.len());
+      buf.resize(len, PADDING_BYTE);
+    }
+
+    let file_size = buf.len() as u32;
+    endianness.write_u16(&mut buf[0x0E..0x10], self.sections.len() as u16).with_context(|_| "could not write section count")?;
+    endianness.write_u32(&mut buf[0x12..0x16], file_size).with_context(|_| "could not write file size")?;
+
+    Ok(buf)
+  }
+}
+
+/// Split the MSBT in `bytes` into an MSBT with only the sections that the msbt crate reads and the
+/// sections that it does not.
+pub fn split_unknown_sections(bytes: &[u8]) -> Result<(Cow<'_, [u8]>, Vec<RawSection>)> {
+  let mut raw = RawMsbt::parse(bytes)?;
+  let unknown = raw.take_unknown_sections();
+  if unknown.is_empty() {
+    return Ok((Cow::Borrowed(bytes), unknown));
+  }
+  Ok((Cow::Owned(raw.to_bytes()?), unknown))
+}
+
+/// Read a hash table of labels, such as LBL1, as each label's name and value in the order they
+/// appear.
+pub fn read_labels(data: &[u8], endianness: Endianness) -> Result<Vec<(String, u32)>> {
+  let read_u32 = |pos: usize| -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or_else(|| failure::format_err!("label table too short"))?;
+    Ok(endianness.read_u32(bytes).with_context(|_| "could not read label table")?)
+  };
+
+  let group_count = read_u32(0)? as usize;
+  let mut labels = Vec::new();
+  for group in 0..group_count {
+    let count = read_u32(4 + group * 8)?;
+    let mut pos = read_u32(8 + group * 8)? as usize;
+    for _ in 0..count {
+      let len = *data.get(pos).ok_or_else(|| failure::format_err!("label table too short"))? as usize;
+      let name = data.get(pos + 1..pos + 1 + len).ok_or_else(|| failure::format_err!("label table too short"))?;
+      let value = read_u32(pos + 1 + len)?;
+      labels.push((String::from_utf8_lossy(name).into_owned(), value));
+      pos += 1 + len + 4;
+    }
+  }
+  Ok(labels)
+}
+
+/// Write a hash table of labels with `group_count` groups. Labels in the same group keep their
+/// order.
+pub fn write_labels(labels: &[(String, u32)], group_count: u32, endianness: Endianness) -> Result<Vec<u8>> {
+  if group_count == 0 {
+    failure::bail!("a label table needs at least one group");
+  }
+  let mut groups = vec![Vec::new(); group_count as usize];
+  for label in labels {
+    groups[label_hash(&label.0, group_count) as usize].push(label);
+  }
+
+  let mut buf = Vec::new();
+  endianness.write_u32(&mut buf, group_count)?;
+  let mut offset = 4 + groups.len() * 8;
+  for group in &groups {
+    endianness.write_u32(&mut buf, group.len() as u32)?;
+    endianness.write_u32(&mut buf, offset as u32)?;
+    offset += group.iter().map(|(name, _)| 1 + name.len() + 4).sum::<usize>();
+  }
+  for &(name, value) in groups.iter().flat_map(|g| g.iter()) {
+    if name.len() > 0xFF {
+      failure::bail!("label {} is longer than 255 bytes", name);
+    }
+    buf.push(name.len() as u8);
+    buf.extend_from_slice(name.as_bytes());
+    endianness.write_u32(&mut buf, *value)?;
+  }
+  Ok(buf)
+}
+
+fn label_hash(name: &str, group_count: u32) -> u32 {
+  name.bytes().fold(0u32, |hash, b| hash.wrapping_mul(0x492).wrapping_add(u32::from(b))) % group_count
+}
+
+fn endianness(header: &[u8]) -> Result<Endianness> {
+  match &header[0x08..0x0A] {
+    [0xFE, 0xFF] => Ok(Endianness::Big),
+    [0xFF, 0xFE] => Ok(Endianness::Little),
+    x => failure::bail!("invalid msbt: bad byte order mark {:?}", x),
+  }
+}
+
+fn align(n: usize) -> usize {
+  n + (SECTION_ALIGNMENT - n % SECTION_ALIGNMENT) % SECTION_ALIGNMENT
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A little-endian MSBT header declaring `sections` sections.
+  fn header(sections: u8) -> Vec<u8> {
+    let mut header = b"MsgStdBn\xFF\xFE\x00\x00\x01\x03".to_vec();
+    header.extend_from_slice(&[sections, 0, 0, 0]);
+    header.extend_from_slice(&[0; HEADER_LEN - 0x12]);
+    header
+  }
+
+  #[test]
+  fn parses_sections() {
+    let mut bytes = header(2);
+    bytes.extend_from_slice(b"ABC1\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00xyz");
+    bytes.extend_from_slice(&[PADDING_BYTE; 13]);
+    bytes.extend_from_slice(b"TXT2\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+
+    let raw = RawMsbt::parse(&bytes).unwrap();
+    assert_eq!(raw.endianness(), Endianness::Little);
+    assert_eq!(raw.version(), 3);
+    assert_eq!(raw.section_order(), vec![*b"ABC1", *b"TXT2"]);
+    assert_eq!(raw.section(b"ABC1").unwrap().data, b"xyz");
+    assert!(raw.section(b"TXT2").unwrap().data.is_empty());
+
+    // the file size is filled in when writing
+    let written = raw.to_bytes().unwrap();
+    assert_eq!(Endianness::Little.read_u32(&written[0x12..0x16]).unwrap() as usize, bytes.len());
+    assert_eq!(written[..0x12], bytes[..0x12]);
+    assert_eq!(written[0x16..], bytes[0x16..]);
+  }
+
+  #[test]
+  fn truncated_sections_are_rejected() {
+    let mut bytes = header(1);
+    bytes.extend_from_slice(b"ABC1\x10\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00xyz");
+    assert!(RawMsbt::parse(&bytes).is_err());
+    assert!(RawMsbt::parse(&bytes[..HEADER_LEN - 1]).is_err());
+  }
+
+  #[test]
+  fn unknown_sections_are_split_and_reordered() {
+    let mut raw = RawMsbt::parse(&header(0)).unwrap();
+    for magic in &[*b"LBL1", *b"ABC1", *b"TXT2"] {
+      raw.sections.push(RawSection { magic: *magic, data: magic.to_vec() });
+    }
+    let order = raw.section_order();
+    let bytes = raw.to_bytes().unwrap();
+
+    let (known, unknown) = split_unknown_sections(&bytes).unwrap();
+    let mut known = RawMsbt::parse(&known).unwrap();
+    assert_eq!(known.section_order(), vec![*b"LBL1", *b"TXT2"]);
+    assert_eq!(unknown.iter().map(|s| s.magic).collect::<Vec<_>>(), vec![*b"ABC1"]);
+
+    known.sections.extend(unknown);
+    known.reorder(&order);
+    assert_eq!(known.to_bytes().unwrap(), bytes);
+  }
+
+  #[test]
+  fn txt2_strings_are_split_at_their_offsets() {
+    let mut raw = RawMsbt::parse(&header(0)).unwrap();
+    let txt2 = b"\x02\x00\x00\x00\x0C\x00\x00\x00\x10\x00\x00\x00a\x00\x00\x00bc\x00\x00".to_vec();
+    raw.sections.push(RawSection { magic: *b"TXT2", data: txt2 });
+
+    let strings = raw.txt2_strings().unwrap();
+    assert_eq!(strings, vec![&b"a\x00\x00\x00"[..], &b"bc\x00\x00"[..]]);
+  }
+
+  #[test]
+  fn labels_are_grouped_by_hash() {
+    assert_eq!(label_hash("a", 101), 0x61);
+    assert_eq!(label_hash("ab", 101), (0x61 * 0x492 + 0x62) % 101);
+
+    let labels = vec![("b".to_string(), 0), ("a".to_string(), 1), ("c".to_string(), 2)];
+    let data = write_labels(&labels, 2, Endianness::Big).unwrap();
+    // a and c hash to group 1, b to group 0
+    assert_eq!(&data[..20], b"\x00\x00\x00\x02\x00\x00\x00\x01\x00\x00\x00\x14\x00\x00\x00\x02\x00\x00\x00\x1A");
+    assert_eq!(read_labels(&data, Endianness::Big).unwrap(), vec![labels[0].clone(), labels[1].clone(), labels[2].clone()]);
+
+    assert!(write_labels(&labels, 0, Endianness::Big).is_err());
+  }
+}
@@ -0,0 +1,19 @@
+//! Control sequences known from Splatoon 2.
+//!
+//! The system controls (type 0) that LibMessageStudio games share are decoded: ruby, text size and
+//! page breaks. Every other control sequence is kept as
+//! [`Control::Unknown`](crate::botw::Control::Unknown).
+
+use crate::game::{ControlParser, Profile, parse_system_control};
+
+/// The control sequences of Splatoon 2.
+#[derive(Debug)]
+pub struct Splatoon2;
+
+impl Profile for Splatoon2 {
+  fn controls(&self) -> &[(u16, ControlParser)] {
+    &[
+      (0x00, parse_system_control),
+    ]
+  }
+}
@@ -1,25 +1,96 @@
+use failure::ResultExt;
 use walkdir::{DirEntry, WalkDir};
 
 use crate::Result;
 
-use std::path::PathBuf;
+use clap::ArgMatches;
 
+use msyt::{
+  archive::{self, Compression, Sarc},
+  game::Game,
+  msbp::Msbp,
+};
+
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+pub mod convert;
 pub mod create;
+pub mod diff;
 pub mod export;
 pub mod import;
+pub mod msbf;
+pub mod verify;
 
 pub use self::{
+  convert::convert,
   create::create,
+  diff::diff,
   export::export,
   import::import,
+  msbf::msbf,
+  verify::verify,
 };
 
-pub fn find_files<'a>(paths: impl Iterator<Item = &'a str>, ext: &str) -> Result<Vec<PathBuf>> {
+pub fn find_files<'a>(paths: impl Iterator<Item = &'a str>, exts: &[&str]) -> Result<Vec<PathBuf>> {
   paths
     .flat_map(|p| WalkDir::new(p)
       .into_iter()
       .map(|e| e.map(DirEntry::into_path))
-      .filter(|p| p.as_ref().map(|p| p.is_file() && p.extension().and_then(std::ffi::OsStr::to_str) == Some(ext)).unwrap_or(false)))
+      .filter(|p| p.as_ref().map(|p| p.is_file() && p.extension().and_then(std::ffi::OsStr::to_str).map(|e| exts.contains(&e)).unwrap_or(false)).unwrap_or(false)))
       .map(|p| p.map_err(Into::into))
     .collect()
 }
+
+/// The game chosen with `--game`, or the MSBP given with `--msbp`.
+pub fn game(matches: &ArgMatches) -> Result<Game> {
+  if let Some(path) = matches.value_of("msbp") {
+    let data = std::fs::read(path).with_context(|_| format!("could not read {}", path))?;
+    let msbp = Msbp::parse(&data).with_context(|_| format!("could not parse msbp {}", path))?;
+    return Ok(Game::Project(Arc::new(msbp)));
+  }
+  let name = matches.value_of("game").expect("clap arg with default");
+  Ok(Game::from_name(name).expect("clap arg with possible values"))
+}
+
+/// The zstd dictionary given with `--zstd-dict`, if any.
+pub fn zstd_dict(matches: &ArgMatches) -> Result<Option<Vec<u8>>> {
+  let path = match matches.value_of("zstd_dict") {
+    Some(p) => p,
+    None => return Ok(None),
+  };
+  let data = std::fs::read(path).with_context(|_| format!("could not read {}", path))?;
+  let dict = archive::read_dictionary(&data).with_context(|_| format!("could not read zstd dictionary {}", path))?;
+  Ok(Some(dict))
+}
+
+pub fn read_archive(path: &Path, dict: Option<&[u8]>) -> Result<(Sarc, Compression)> {
+  let data = std::fs::read(path).with_context(|_| format!("could not read {}", path.to_string_lossy()))?;
+  archive::open_with_dict(&data, dict)
+    .with_context(|_| format!("could not open archive {}", path.to_string_lossy()))
+    .map_err(Into::into)
+}
+
+pub fn write_archive(path: &Path, sarc: &Sarc, compression: Compression, dict: Option<&[u8]>, backup: bool) -> Result<()> {
+  let data = archive::write_with_dict(sarc, compression, dict)
+    .with_context(|_| format!("could not create archive {}", path.to_string_lossy()))?;
+
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)
+      .with_context(|_| format!("could not create directory {}", parent.to_string_lossy()))?;
+  }
+
+  if backup && path.exists() {
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+    let backup_path = path.with_extension(format!("{}.bak", extension));
+    std::fs::rename(path, &backup_path)
+      .with_context(|_| format!("could not backup {} to {}", path.to_string_lossy(), backup_path.to_string_lossy()))?;
+  }
+
+  std::fs::write(path, data)
+    .with_context(|_| format!("could not write archive to {}", path.to_string_lossy()))?;
+
+  Ok(())
+}
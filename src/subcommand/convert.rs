@@ -0,0 +1,140 @@
+use byteordered::Endianness;
+use clap::ArgMatches;
+use failure::ResultExt;
+use msbt::Encoding;
+use rayon::prelude::*;
+
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  Result,
+  subcommand::{find_files, game, read_archive, write_archive, zstd_dict},
+};
+
+use msyt::{
+  archive,
+  convert::Conversion,
+};
+
+pub fn convert(matches: &ArgMatches) -> Result<()> {
+  let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
+  let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
+    let mut exts = vec!["msbt"];
+    exts.extend(archive::EXTENSIONS);
+    let mut paths = find_files(input_paths.iter().cloned(), &exts)?;
+    // .zs is also used for files that are not archives
+    paths.retain(|p| p.extension().map(|e| e == "msbt").unwrap_or(false) || archive::is_archive(p));
+    paths
+  } else {
+    input_paths.iter().map(PathBuf::from).collect()
+  };
+  let output_path = matches.value_of("output").map(Path::new);
+  let backup = !matches.is_present("no-backup");
+  let dict = zstd_dict(matches)?;
+
+  let conversion = Conversion {
+    game: game(matches)?,
+    endianness: matches.value_of("platform").map(|p| match p {
+      "switch" => Endianness::Little,
+      "wiiu" => Endianness::Big,
+      _ => unreachable!("clap arg with possible values"),
+    }),
+    encoding: matches.value_of("encoding").map(|e| match e {
+      "utf16" => Encoding::Utf16,
+      "utf8" => Encoding::Utf8,
+      _ => unreachable!("clap arg with possible values"),
+    }),
+    icons: match matches.values_of("icon") {
+      Some(icons) => icons.map(parse_icon_mapping).collect::<Result<HashMap<_, _>>>()?,
+      None => HashMap::new(),
+    },
+    keep_opaque: matches.is_present("keep_opaque"),
+  };
+
+  paths
+    .into_par_iter()
+    .map(|path| {
+      let dest = match output_path {
+        Some(output) => {
+          let stripped_path = match input_paths.iter().flat_map(|input| path.strip_prefix(input)).next() {
+            Some(s) => s,
+            None => failure::bail!("no input path works as a prefix on {}", path.to_string_lossy()),
+          };
+          output.join(stripped_path)
+        },
+        None => path.clone(),
+      };
+
+      if archive::is_archive(&path) {
+        return convert_archive(&conversion, &path, &dest, dict.as_deref(), backup);
+      }
+
+      let msbt = std::fs::read(&path).with_context(|_| format!("could not read {}", path.to_string_lossy()))?;
+      let (converted, opaque) = conversion.convert(&msbt)
+        .with_context(|_| format!("could not convert {}", path.to_string_lossy()))?;
+      warn_opaque(&path.to_string_lossy(), &opaque);
+
+      if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+          .with_context(|_| format!("could not create directory {}", parent.to_string_lossy()))?;
+      }
+
+      if backup && dest.exists() {
+        let backup_path = dest.with_extension("msbt.bak");
+        std::fs::rename(&dest, &backup_path)
+          .with_context(|_| format!("could not backup {} to {}", dest.to_string_lossy(), backup_path.to_string_lossy()))?;
+      }
+
+      std::fs::write(&dest, converted)
+        .with_context(|_| format!("could not write msbt to {}", dest.to_string_lossy()))?;
+
+      Ok(())
+    })
+    .collect::<Result<_>>()
+}
+
+/// Convert every MSBT inside of the archive at `path`, along with the archive itself.
+fn convert_archive(conversion: &Conversion, path: &Path, dest: &Path, dict: Option<&[u8]>, backup: bool) -> Result<()> {
+  let (mut sarc, compression) = read_archive(path, dict)?;
+
+  for (name, data) in sarc.files.iter_mut().filter(|(name, _)| name.ends_with(".msbt")) {
+    let (converted, opaque) = conversion.convert(data)
+      .with_context(|_| format!("could not convert {} in {}", name, path.to_string_lossy()))?;
+    warn_opaque(&format!("{} in {}", name, path.to_string_lossy()), &opaque);
+    *data = converted;
+  }
+  if let Some(endianness) = conversion.endianness {
+    sarc.endianness = endianness;
+  }
+
+  write_archive(dest, &sarc, compression, dict, backup)
+}
+
+fn warn_opaque(msbt: &str, opaque: &[String]) {
+  if opaque.is_empty() {
+    return;
+  }
+
+  let mut report = format!("warning: {} parts of {} could not be converted and were copied unchanged:", opaque.len(), msbt);
+  for part in opaque {
+    report.push_str(&format!("\n  {}", part));
+  }
+  eprintln!("{}", report);
+}
+
+/// Parse an icon mapping given as `from:to`.
+fn parse_icon_mapping(mapping: &str) -> Result<(u8, u8)> {
+  let mut parts = mapping.splitn(2, ':');
+  let mut next = || -> Result<u8> {
+    let part = parts.next().unwrap_or_default();
+    part.parse::<u8>()
+      .with_context(|_| format!("invalid icon mapping {}: expected from:to with values from 0 to 255", mapping))
+      .map_err(Into::into)
+  };
+  let from = next()?;
+  let to = next()?;
+  Ok((from, to))
+}
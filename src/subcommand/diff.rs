@@ -0,0 +1,50 @@
+use clap::ArgMatches;
+use failure::ResultExt;
+
+use std::{
+  fs::File,
+  io::BufReader,
+  path::Path,
+};
+
+use crate::{
+  Result,
+  subcommand::game,
+};
+
+use msyt::{Msyt, diff::MsytDiff, game::Game};
+
+pub fn diff(matches: &ArgMatches) -> Result<()> {
+  let old_path = Path::new(matches.value_of("old").expect("required clap arg"));
+  let new_path = Path::new(matches.value_of("new").expect("required clap arg"));
+
+  let game = game(matches)?;
+
+  let old = load(old_path, &game)?;
+  let new = load(new_path, &game)?;
+
+  let diff = MsytDiff::new(&old, &new);
+
+  if matches.is_present("json") {
+    let json = serde_json::to_string_pretty(&diff).with_context(|_| "could not serialise diff to json")?;
+    println!("{}", json);
+  } else if diff.is_empty() {
+    println!("no differences");
+  } else {
+    print!("{}", diff);
+  }
+
+  Ok(())
+}
+
+/// Load an MSYT from either an MSBT or an MSYT file, depending on the extension of `path`.
+pub fn load(path: &Path, game: &Game) -> Result<Msyt> {
+  let file = File::open(path).with_context(|_| format!("could not open {}", path.to_string_lossy()))?;
+  let msyt = match path.extension().and_then(std::ffi::OsStr::to_str) {
+    Some("msyt") => serde_yaml::from_reader(BufReader::new(file))
+      .with_context(|_| format!("could not read valid yaml from {}", path.to_string_lossy()))?,
+    _ => Msyt::from_msbt_reader(BufReader::new(file), game)
+      .with_context(|_| format!("could not read msbt from {}", path.to_string_lossy()))?,
+  };
+  Ok(msyt)
+}
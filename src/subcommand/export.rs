@@ -1,7 +1,5 @@
 use clap::ArgMatches;
 use failure::ResultExt;
-use indexmap::IndexMap;
-use msbt::{Msbt, section::Atr1};
 use rayon::prelude::*;
 
 use std::{
@@ -12,95 +10,109 @@ use std::{
 
 use crate::{
   Result,
-  model::{MsbtInfo, Msyt, Entry},
-  subcommand::find_files,
+  subcommand::{find_files, game, read_archive, zstd_dict},
 };
 
+use msyt::{Msyt, archive, game::Game};
+
 pub fn export(matches: &ArgMatches) -> Result<()> {
   let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
   let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
-    find_files(input_paths.iter().map(Clone::clone), "msbt")?
+    let mut exts = vec!["msbt"];
+    exts.extend(archive::EXTENSIONS);
+    let mut paths = find_files(input_paths.iter().map(Clone::clone), &exts)?;
+    // .zs is also used for files that are not archives
+    paths.retain(|p| p.extension().map(|e| e == "msbt").unwrap_or(false) || archive::is_archive(p));
+    paths
   } else {
     input_paths.iter().map(PathBuf::from).collect()
   };
   let output_path = matches.value_of("output").map(Path::new);
+  let options = Options {
+    sort: matches.is_present("sort"),
+    pages: matches.is_present("pages"),
+  };
+  let game = game(matches)?;
+  let dict = zstd_dict(matches)?;
 
   paths
     .into_par_iter()
     .map(|path| {
-      let msbt_file = File::open(&path).with_context(|_| format!("could not open {}", path.to_string_lossy()))?;
-      let msbt = Msbt::from_reader(BufReader::new(msbt_file))
-        .with_context(|_| format!("could not read msbt file at {}", path.to_string_lossy()))?;
-
-      let lbl1 = match msbt.lbl1() {
-        Some(lbl) => lbl,
-        None => failure::bail!("invalid msbt: missing lbl1: {}", path.to_string_lossy()),
-      };
-
-      let mut entries = IndexMap::with_capacity(lbl1.labels().len());
-
-      for label in lbl1.labels() {
-        let mut all_content = Vec::new();
-
-        let raw_value = label.value_raw()
-          .ok_or_else(|| failure::format_err!(
-            "invalid msbt at {}: missing string for label {}",
-            path.to_string_lossy(),
-            label.name(),
-          ))?;
-        let mut parts = crate::botw::parse_controls(msbt.header(), raw_value)
-          .with_context(|_| format!("could not parse control sequences in {}", path.to_string_lossy()))?;
-        all_content.append(&mut parts);
-        let entry = Entry {
-          attributes: msbt.atr1()
-            .and_then(|a| a.strings()
-              .get(label.index() as usize)
-              .map(|s| crate::util::strip_nul(*s))
-              .map(ToString::to_string)),
-          contents: all_content,
-        };
-        entries.insert(label.name().to_string(), entry);
-      }
-
-      entries.sort_keys();
-
-      let msyt = Msyt {
-        entries,
-        msbt: MsbtInfo {
-          group_count: lbl1.group_count(),
-          atr1_unknown: msbt.atr1().map(Atr1::unknown_1),
-          ato1: msbt.ato1().map(|a| a.unknown_bytes().to_vec()),
-          tsy1: msbt.tsy1().map(|a| a.unknown_bytes().to_vec()),
-          nli1: msbt.nli1().map(|a| crate::model::Nli1 {
-            id_count: a.id_count(),
-            global_ids: a.global_ids().clone(),
-          }),
-        },
-      };
-
       let dest = match output_path {
         Some(output) => {
           let stripped_path = match input_paths.iter().flat_map(|input| path.strip_prefix(input)).next() {
             Some(s) => s,
             None => failure::bail!("no input path works as a prefix on {}", path.to_string_lossy()),
           };
-          output.join(stripped_path).with_extension("msyt")
+          output.join(stripped_path)
         },
-        None => path.with_extension("msyt"),
+        None => path.clone(),
       };
-      if let Some(parent) = dest.parent() {
-        std::fs::create_dir_all(parent)
-          .with_context(|_| format!("could not create parent directories for {}", parent.to_string_lossy()))?;
+
+      if archive::is_archive(&path) {
+        return export_archive(&path, &archive::unpacked_path(&dest), options, &game, dict.as_deref());
       }
-      let mut writer = BufWriter::new(File::create(dest)?);
-      serde_yaml::to_writer(
-        &mut writer,
-        &msyt,
-      ).with_context(|_| "could not write yaml to file")?;
-      // add final newline
-      writer.write_all(b"\n").with_context(|_| "could not write final newline to file")?;
 
-      Ok(())
+      let msbt_file = File::open(&path).with_context(|_| format!("could not open {}", path.to_string_lossy()))?;
+      let mut msyt = Msyt::from_msbt_reader(BufReader::new(msbt_file), &game)
+        .with_context(|_| format!("could not export {}", path.to_string_lossy()))?;
+      options.apply(&mut msyt);
+
+      write_msyt(&msyt, &dest.with_extension("msyt"))
     })
     .collect::<Result<_>>()
 }
+
+/// How to lay out exported MSYTs.
+#[derive(Clone, Copy)]
+struct Options {
+  /// Sort entries by label.
+  sort: bool,
+  /// Split contents into pages at page breaks.
+  pages: bool,
+}
+
+impl Options {
+  fn apply(self, msyt: &mut Msyt) {
+    if self.sort {
+      msyt.sort_entries();
+    }
+    if self.pages {
+      msyt.split_pages();
+    }
+  }
+}
+
+/// Export every MSBT inside of the archive at `path` into the directory `dest`.
+fn export_archive(path: &Path, dest: &Path, options: Options, game: &Game, dict: Option<&[u8]>) -> Result<()> {
+  let (sarc, _) = read_archive(path, dict)?;
+
+  sarc.files
+    .iter()
+    .filter(|(name, _)| name.ends_with(".msbt"))
+    .try_for_each(|(name, data)| {
+      let mut msyt = Msyt::from_msbt_bytes(data, game)
+        .with_context(|_| format!("could not export {} in {}", name, path.to_string_lossy()))?;
+      options.apply(&mut msyt);
+
+      let entry_dest = archive::entry_path(dest, name)
+        .with_context(|_| format!("could not export {} in {}", name, path.to_string_lossy()))?;
+      write_msyt(&msyt, &entry_dest.with_extension("msyt"))
+    })
+}
+
+fn write_msyt(msyt: &Msyt, dest: &Path) -> Result<()> {
+  if let Some(parent) = dest.parent() {
+    std::fs::create_dir_all(parent)
+      .with_context(|_| format!("could not create parent directories for {}", parent.to_string_lossy()))?;
+  }
+  let mut writer = BufWriter::new(File::create(dest)?);
+  serde_yaml::to_writer(
+    &mut writer,
+    msyt,
+  ).with_context(|_| "could not write yaml to file")?;
+  // add final newline
+  writer.write_all(b"\n").with_context(|_| "could not write final newline to file")?;
+
+  Ok(())
+}
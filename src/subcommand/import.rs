@@ -1,23 +1,24 @@
 use clap::ArgMatches;
-use msbt::Msbt;
+use failure::ResultExt;
 use rayon::prelude::*;
 
 use std::{
   fs::File,
-  io::{BufReader, BufWriter},
+  io::BufReader,
   path::{Path, PathBuf},
 };
 
 use crate::{
   Result,
-  model::{Msyt, Content},
-  subcommand::find_files,
+  subcommand::{find_files, read_archive, write_archive, zstd_dict},
 };
 
+use msyt::{Msyt, archive};
+
 pub fn import(matches: &ArgMatches) -> Result<()> {
   let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
   let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
-    find_files(input_paths.iter().map(Clone::clone), "msyt")?
+    find_files(input_paths.iter().map(Clone::clone), &["msyt"])?
   } else {
     input_paths.iter().map(PathBuf::from).collect()
   };
@@ -25,6 +26,12 @@ pub fn import(matches: &ArgMatches) -> Result<()> {
 
   let extension = matches.value_of("extension").expect("clap arg with default");
   let backup = !matches.is_present("no-backup");
+  let prune = matches.is_present("prune");
+
+  if let Some(archive_path) = matches.value_of("archive").map(Path::new) {
+    let dict = zstd_dict(matches)?;
+    return import_archive(archive_path, &input_paths, paths, output_path, dict.as_deref(), backup, prune);
+  }
 
   paths
     .into_par_iter()
@@ -33,20 +40,11 @@ pub fn import(matches: &ArgMatches) -> Result<()> {
       let msyt: Msyt = serde_yaml::from_reader(BufReader::new(msyt_file))?;
 
       let msbt_path = path.with_extension("msbt");
-      let msbt_file = File::open(&msbt_path)?;
-
-      let mut msbt = Msbt::from_reader(BufReader::new(msbt_file))?;
-
-      for (key, entry) in msyt.entries {
-        let new_val = Content::write_all(msbt.header(), &entry.contents)?;
-        if let Some(ref mut lbl1) = msbt.lbl1_mut() {
-          if let Some(label) = lbl1.labels_mut().iter_mut().find(|x| x.name() == key) {
-            if let Err(()) = label.set_value_raw(new_val) {
-              failure::bail!("could not set raw string at index {}", label.index());
-            }
-          }
-        }
-      }
+      let msbt = std::fs::read(&msbt_path)?;
+
+      let (new_msbt, missing) = msyt.import_into_bytes(&msbt, prune)
+        .with_context(|_| format!("could not import {} into {}", path.to_string_lossy(), msbt_path.to_string_lossy()))?;
+      report_missing(&msbt_path.to_string_lossy(), &missing, prune);
 
       let dest_path = match output_path {
         Some(output) => {
@@ -67,10 +65,67 @@ pub fn import(matches: &ArgMatches) -> Result<()> {
         std::fs::rename(&dest_path, backup_path)?;
       }
 
-      let new_msbt = File::create(&dest_path)?;
-      msbt.write_to(BufWriter::new(new_msbt))?;
+      std::fs::write(&dest_path, new_msbt)?;
 
       Ok(())
     })
     .collect::<Result<_>>()
 }
+
+/// Import MSYT files into the MSBT files inside of the archive at `archive_path`. The MSBT for
+/// each MSYT is found at the same relative path inside of the archive.
+fn import_archive(archive_path: &Path, input_paths: &[&str], paths: Vec<PathBuf>, output_path: Option<&Path>, dict: Option<&[u8]>, backup: bool, prune: bool) -> Result<()> {
+  let (mut sarc, compression) = read_archive(archive_path, dict)?;
+
+  let imported: Vec<(String, Vec<u8>)> = paths
+    .into_par_iter()
+    .map(|path| {
+      let msyt_file = File::open(&path).with_context(|_| format!("could not open file {}", path.to_string_lossy()))?;
+      let msyt: Msyt = serde_yaml::from_reader(BufReader::new(msyt_file))
+        .with_context(|_| format!("could not read valid yaml from {}", path.to_string_lossy()))?;
+
+      let stripped_path = match input_paths.iter().flat_map(|input| path.strip_prefix(input)).next() {
+        Some(s) => s,
+        None => failure::bail!("no input path works as a prefix on {}", path.to_string_lossy()),
+      };
+      let name = archive::file_name(&stripped_path.with_extension("msbt"));
+      let data = match sarc.files.get(&name) {
+        Some(d) => d,
+        None => failure::bail!("{} does not contain {}", archive_path.to_string_lossy(), name),
+      };
+
+      let (new_msbt, missing) = msyt.import_into_bytes(data, prune)
+        .with_context(|_| format!("could not import {} into {} in {}", path.to_string_lossy(), name, archive_path.to_string_lossy()))?;
+      report_missing(&format!("{} in {}", name, archive_path.to_string_lossy()), &missing, prune);
+
+      Ok((name, new_msbt))
+    })
+    .collect::<Result<_>>()?;
+
+  for (name, msbt) in imported {
+    sarc.files.insert(name, msbt);
+  }
+
+  let dest_path = match output_path {
+    Some(output) => match archive_path.file_name() {
+      Some(file_name) => output.join(file_name),
+      None => failure::bail!("invalid archive path {}", archive_path.to_string_lossy()),
+    },
+    None => archive_path.to_path_buf(),
+  };
+
+  write_archive(&dest_path, &sarc, compression, dict, backup)
+}
+
+fn report_missing(msbt: &str, missing: &[String], pruned: bool) {
+  if missing.is_empty() {
+    return;
+  }
+
+  let action = if pruned { "removed" } else { "kept unchanged (use --prune to remove them)" };
+  let mut report = format!("warning: {} labels in {} are not in the msyt and were {}:", missing.len(), msbt, action);
+  for label in missing {
+    report.push_str(&format!("\n  {}", label));
+  }
+  eprintln!("{}", report);
+}
@@ -0,0 +1,134 @@
+use clap::ArgMatches;
+use failure::ResultExt;
+use rayon::prelude::*;
+
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter, Write},
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  Result,
+  subcommand::{diff::load, find_files, game},
+};
+
+use msyt::{game::Game, msbf::Msbf};
+
+/// The extension of exported MSBFs, added after `.msbf`.
+const EXTENSION: &str = "yml";
+
+pub fn msbf(matches: &ArgMatches) -> Result<()> {
+  match matches.subcommand() {
+    ("export", Some(sub_matches)) => export(sub_matches),
+    ("create", Some(sub_matches)) => create(sub_matches),
+    _ => unreachable!("clap allowed an unspecified subcommand"),
+  }
+}
+
+fn export(matches: &ArgMatches) -> Result<()> {
+  let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
+  let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
+    find_files(input_paths.iter().cloned(), &["msbf"])?
+  } else {
+    input_paths.iter().map(PathBuf::from).collect()
+  };
+  let output_path = matches.value_of("output").map(Path::new);
+  let game = game(matches)?;
+
+  paths
+    .into_par_iter()
+    .map(|path| {
+      let dest = destination(output_path, &input_paths, &path)?;
+
+      // the msbt or msyt next to the msbf, if any, names the messages
+      let paired = [path.with_extension("msbt"), path.with_extension("msyt")]
+        .iter()
+        .find(|p| p.exists())
+        .map(|p| load(p, &game))
+        .transpose()?;
+
+      let data = std::fs::read(&path).with_context(|_| format!("could not read {}", path.to_string_lossy()))?;
+      let msbf = Msbf::from_bytes(&data, paired.as_ref())
+        .with_context(|_| format!("could not export {}", path.to_string_lossy()))?;
+
+      let dest = dest.with_extension(format!("msbf.{}", EXTENSION));
+      if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+          .with_context(|_| format!("could not create parent directories for {}", parent.to_string_lossy()))?;
+      }
+      let mut writer = BufWriter::new(File::create(&dest)?);
+      serde_yaml::to_writer(&mut writer, &msbf).with_context(|_| "could not write yaml to file")?;
+      // add final newline
+      writer.write_all(b"\n").with_context(|_| "could not write final newline to file")?;
+
+      Ok(())
+    })
+    .collect::<Result<_>>()
+}
+
+fn create(matches: &ArgMatches) -> Result<()> {
+  let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
+  let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
+    let mut paths = find_files(input_paths.iter().cloned(), &[EXTENSION])?;
+    paths.retain(|p| p.file_stem().map(|s| Path::new(s).extension().map(|e| e == "msbf").unwrap_or(false)).unwrap_or(false));
+    paths
+  } else {
+    input_paths.iter().map(PathBuf::from).collect()
+  };
+  let output_path = matches.value_of("output").map(Path::new);
+  let backup = !matches.is_present("no-backup");
+
+  paths
+    .into_par_iter()
+    .map(|path| {
+      // foo.msbf.yml becomes foo.msbf
+      let dest = destination(output_path, &input_paths, &path)?.with_extension("");
+
+      let file = File::open(&path).with_context(|_| format!("could not open file {}", path.to_string_lossy()))?;
+      let mut msbf: Msbf = serde_yaml::from_reader(BufReader::new(file))
+        .with_context(|_| format!("could not read valid yaml from {}", path.to_string_lossy()))?;
+
+      // the msbt or msyt next to the exported msbf, if any, gives the message of each label. only
+      // the labels are needed, so control sequences are not decoded
+      let msbf_path = path.with_extension("");
+      let paired = [msbf_path.with_extension("msbt"), msbf_path.with_extension("msyt")]
+        .iter()
+        .find(|p| p.exists())
+        .map(|p| load(p, &Game::Generic).map(|msyt| (p.clone(), msyt)))
+        .transpose()?;
+      if let Some((paired_path, msyt)) = paired {
+        msbf.apply_labels(&msyt)
+          .with_context(|_| format!("could not find messages for {} in {}", path.to_string_lossy(), paired_path.to_string_lossy()))?;
+      }
+      let data = msbf.into_bytes()
+        .with_context(|_| format!("could not create msbf from {}", path.to_string_lossy()))?;
+
+      if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+          .with_context(|_| format!("could not create directory {}", parent.to_string_lossy()))?;
+      }
+      if backup && dest.exists() {
+        let backup_path = dest.with_extension("msbf.bak");
+        std::fs::rename(&dest, &backup_path)
+          .with_context(|_| format!("could not backup {} to {}", dest.to_string_lossy(), backup_path.to_string_lossy()))?;
+      }
+      std::fs::write(&dest, data)
+        .with_context(|_| format!("could not write msbf to {}", dest.to_string_lossy()))?;
+
+      Ok(())
+    })
+    .collect::<Result<_>>()
+}
+
+/// Where to write the file made from `path`, without changing its extension.
+fn destination(output: Option<&Path>, input_paths: &[&str], path: &Path) -> Result<PathBuf> {
+  let output = match output {
+    Some(o) => o,
+    None => return Ok(path.to_path_buf()),
+  };
+  match input_paths.iter().flat_map(|input| path.strip_prefix(input)).next() {
+    Some(s) => Ok(output.join(s)),
+    None => failure::bail!("no input path works as a prefix on {}", path.to_string_lossy()),
+  }
+}
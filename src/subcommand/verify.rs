@@ -0,0 +1,87 @@
+use clap::ArgMatches;
+use failure::ResultExt;
+use rayon::prelude::*;
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+  Result,
+  subcommand::{find_files, game},
+};
+
+use msyt::verify::{self, Divergence};
+
+pub fn verify(matches: &ArgMatches) -> Result<()> {
+  let input_paths: Vec<&str> = matches.values_of("paths").expect("required clap arg").collect();
+  let paths: Vec<PathBuf> = if matches.is_present("dir_mode") {
+    find_files(input_paths.iter().map(Clone::clone), &["msbt"])?
+  } else {
+    input_paths.iter().map(PathBuf::from).collect()
+  };
+  let compare_path = matches.value_of("compare").map(Path::new);
+  let game = game(matches)?;
+
+  let failed = paths
+    .into_par_iter()
+    .map(|path| {
+      let stripped_path = match input_paths.iter().flat_map(|input| path.strip_prefix(input)).next() {
+        Some(s) => s,
+        None => failure::bail!("no input path works as a prefix on {}", path.to_string_lossy()),
+      };
+      let name = if stripped_path.as_os_str().is_empty() {
+        path.to_string_lossy()
+      } else {
+        stripped_path.to_string_lossy()
+      };
+
+      let bytes = std::fs::read(&path).with_context(|_| format!("could not read {}", path.to_string_lossy()))?;
+      let divergences = match compare_path {
+        Some(compare) => compare_files(&bytes, &rebuilt_path(compare, &path, stripped_path))?,
+        None => verify::round_trip(&bytes, &game)
+          .with_context(|_| format!("could not round-trip {}", path.to_string_lossy()))?,
+      };
+
+      if divergences.is_empty() {
+        println!("{}: msbts are functionally equivalent", name);
+        return Ok(false);
+      }
+
+      let mut report = format!("{}: msbts are NOT functionally equivalent", name);
+      for divergence in divergences {
+        report.push_str(&format!("\n  {}", divergence));
+      }
+      println!("{}", report);
+
+      Ok(true)
+    })
+    .collect::<Result<Vec<bool>>>()?
+    .into_iter()
+    .filter(|&failed| failed)
+    .count();
+
+  if failed > 0 {
+    failure::bail!("{} file(s) are not functionally equivalent", failed);
+  }
+
+  Ok(())
+}
+
+/// The path of the rebuilt MSBT to compare `path` to. If `path` was given directly as an input, it
+/// is compared to `compare` itself, or to the file of the same name if `compare` is a directory.
+fn rebuilt_path(compare: &Path, path: &Path, stripped_path: &Path) -> PathBuf {
+  if !stripped_path.as_os_str().is_empty() {
+    return compare.join(stripped_path);
+  }
+  match path.file_name() {
+    Some(file_name) if compare.is_dir() => compare.join(file_name),
+    _ => compare.to_path_buf(),
+  }
+}
+
+fn compare_files(original: &[u8], rebuilt_path: &Path) -> Result<Vec<Divergence>> {
+  let rebuilt = std::fs::read(rebuilt_path).with_context(|_| format!("could not read {}", rebuilt_path.to_string_lossy()))?;
+
+  verify::compare_bytes(original, &rebuilt)
+    .with_context(|_| format!("could not compare to {}", rebuilt_path.to_string_lossy()))
+    .map_err(Into::into)
+}
@@ -2,11 +2,7 @@ pub mod option_serde_base64;
 pub mod serde_base64;
 
 pub fn strip_nul(s: &str) -> &str {
-  if s.ends_with('\u{0000}') {
-    &s[..s.len() - 1]
-  } else {
-    s
-  }
+  s.strip_suffix('\u{0000}').unwrap_or(s)
 }
 
 pub fn append_nul(mut s: String) -> String {
@@ -1,4 +1,5 @@
-use serde::de::{self, Visitor};
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
 
 use std::fmt::{self, Formatter};
 
@@ -19,15 +20,15 @@ impl<'de> Visitor<'de> for Base64Visitor {
   }
 }
 
-// pub fn serialize<T, S>(data: &T, ser: S) -> Result<S::Ok, S::Error>
-//   where S: Serializer,
-//         T: AsRef<[u8]> + ?Sized,
-// {
-//   ser.serialize_str(&base64::encode(data))
-// }
+pub fn serialize<T, S>(data: &T, ser: S) -> Result<S::Ok, S::Error>
+  where S: Serializer,
+        T: AsRef<[u8]> + ?Sized,
+{
+  ser.serialize_str(&base64::encode(data))
+}
 
-// pub fn deserialize<'de, D>(des: D) -> Result<Vec<u8>, D::Error>
-//   where D: Deserializer<'de>,
-// {
-//   des.deserialize_string(Base64Visitor)
-// }
+pub fn deserialize<'de, D>(des: D) -> Result<Vec<u8>, D::Error>
+  where D: Deserializer<'de>,
+{
+  des.deserialize_string(Base64Visitor)
+}
@@ -0,0 +1,299 @@
+use crate::{
+  Msyt,
+  Result,
+  game::Game,
+  raw::{RawMsbt, RawSection},
+};
+
+use failure::ResultExt;
+use msbt::Msbt;
+
+use std::{
+  collections::BTreeMap,
+  fmt::{self, Display, Formatter},
+  io::Cursor,
+};
+
+/// A difference between an original MSBT and one rebuilt from it that would change how the game
+/// reads the file.
+#[derive(Debug)]
+pub enum Divergence {
+  MissingLbl1,
+  GroupCount { original: u32, rebuilt: u32 },
+  LabelMissing { label: String },
+  LabelAdded { label: String },
+  Txt2 { label: String, offset: usize, original: Option<u8>, rebuilt: Option<u8> },
+  Atr1 { label: String, original: Option<String>, rebuilt: Option<String> },
+  Atr1Unknown { original: Option<u32>, rebuilt: Option<u32> },
+  Ato1,
+  Tsy1,
+  Nli1 { global_id: u32, original: Option<String>, rebuilt: Option<String> },
+  /// A section compared as raw bytes differs, with the lengths of the original and rebuilt
+  /// sections.
+  Section { magic: String, original: Option<usize>, rebuilt: Option<usize> },
+}
+
+impl Display for Divergence {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match *self {
+      Divergence::MissingLbl1 => f.write_str("lbl1: missing"),
+      Divergence::GroupCount { original, rebuilt } => write!(f, "lbl1: group count {} became {}", original, rebuilt),
+      Divergence::LabelMissing { ref label } => write!(f, "lbl1: label {} is missing", label),
+      Divergence::LabelAdded { ref label } => write!(f, "lbl1: label {} was added", label),
+      Divergence::Txt2 { ref label, offset, original, rebuilt } => write!(
+        f,
+        "txt2: label {} differs at byte {:#x} ({} became {})",
+        label,
+        offset,
+        DisplayByte(original),
+        DisplayByte(rebuilt),
+      ),
+      Divergence::Atr1 { ref label, ref original, ref rebuilt } => write!(f, "atr1: label {} attributes {:?} became {:?}", label, original, rebuilt),
+      Divergence::Atr1Unknown { original, rebuilt } => write!(f, "atr1: unknown value {:?} became {:?}", original, rebuilt),
+      Divergence::Ato1 => f.write_str("ato1: bytes differ"),
+      Divergence::Tsy1 => f.write_str("tsy1: bytes differ"),
+      Divergence::Nli1 { global_id, ref original, ref rebuilt } => write!(f, "nli1: global id {} pointed to {:?} but now points to {:?}", global_id, original, rebuilt),
+      Divergence::Section { ref magic, original, rebuilt } => write!(f, "{}: {} became {}", magic.to_lowercase(), DisplaySize(original), DisplaySize(rebuilt)),
+    }
+  }
+}
+
+struct DisplayByte(Option<u8>);
+
+impl Display for DisplayByte {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self.0 {
+      Some(b) => write!(f, "{:#04x}", b),
+      None => f.write_str("end of string"),
+    }
+  }
+}
+
+struct DisplaySize(Option<usize>);
+
+impl Display for DisplaySize {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self.0 {
+      Some(len) => write!(f, "{} bytes", len),
+      None => f.write_str("absent"),
+    }
+  }
+}
+
+/// Round-trip the MSBT in `bytes` through an MSYT, rebuilding it with the original header's byte
+/// order and encoding, and compare the result to the original.
+pub fn round_trip(bytes: &[u8], game: &Game) -> Result<Vec<Divergence>> {
+  let msyt = Msyt::from_msbt_bytes(bytes, game).with_context(|_| "could not export msbt")?;
+  let endianness = msyt.msbt.platform.expect("platform recorded when exporting").endianness();
+  let encoding = msyt.msbt.encoding.expect("encoding recorded when exporting").into();
+  let rebuilt = msyt.into_msbt_bytes(endianness, encoding)
+    .with_context(|_| "could not create msbt")?;
+
+  compare_bytes(bytes, &rebuilt)
+}
+
+/// Compare the MSBTs in `original` and `rebuilt`, including any sections that the msbt crate does
+/// not read.
+///
+/// If neither MSBT has a LBL1 section, TXT2 strings are compared by index and named `#<index>`,
+/// and every other section is compared as raw bytes.
+pub fn compare_bytes(original: &[u8], rebuilt: &[u8]) -> Result<Vec<Divergence>> {
+  let mut original = RawMsbt::parse(original).with_context(|_| "could not read original msbt sections")?;
+  let mut rebuilt = RawMsbt::parse(rebuilt).with_context(|_| "could not read rebuilt msbt sections")?;
+
+  if original.section(b"LBL1").is_none() && rebuilt.section(b"LBL1").is_none() {
+    return compare_unlabelled(&original, &rebuilt);
+  }
+
+  let original_unknown = original.take_unknown_sections();
+  let rebuilt_unknown = rebuilt.take_unknown_sections();
+
+  let original = Msbt::from_reader(Cursor::new(original.to_bytes()?)).with_context(|_| "could not read original msbt")?;
+  let rebuilt = Msbt::from_reader(Cursor::new(rebuilt.to_bytes()?)).with_context(|_| "could not read rebuilt msbt")?;
+  let mut divergences = compare(&original, &rebuilt);
+  compare_sections(&original_unknown, &rebuilt_unknown, &mut divergences);
+
+  Ok(divergences)
+}
+
+fn compare_unlabelled(original: &RawMsbt, rebuilt: &RawMsbt) -> Result<Vec<Divergence>> {
+  let mut divergences = Vec::new();
+
+  let orig_strings = original.txt2_strings().with_context(|_| "could not read original txt2")?;
+  let new_strings = rebuilt.txt2_strings().with_context(|_| "could not read rebuilt txt2")?;
+  for (i, orig_value) in orig_strings.iter().enumerate() {
+    let label = format!("#{}", i);
+    let new_value = match new_strings.get(i) {
+      Some(v) => v,
+      None => {
+        divergences.push(Divergence::LabelMissing { label });
+        continue;
+      },
+    };
+    if orig_value != new_value {
+      let offset = orig_value.iter()
+        .zip(new_value.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| std::cmp::min(orig_value.len(), new_value.len()));
+      divergences.push(Divergence::Txt2 {
+        label,
+        offset,
+        original: orig_value.get(offset).cloned(),
+        rebuilt: new_value.get(offset).cloned(),
+      });
+    }
+  }
+  for i in orig_strings.len()..new_strings.len() {
+    divergences.push(Divergence::LabelAdded { label: format!("#{}", i) });
+  }
+
+  let other_sections = |raw: &RawMsbt| -> Vec<RawSection> {
+    raw.sections.iter()
+      .filter(|s| s.magic != *b"TXT2")
+      .cloned()
+      .collect()
+  };
+  compare_sections(&other_sections(original), &other_sections(rebuilt), &mut divergences);
+
+  Ok(divergences)
+}
+
+/// Compare sections by magic as raw bytes.
+fn compare_sections(original: &[RawSection], rebuilt: &[RawSection], divergences: &mut Vec<Divergence>) {
+  for section in original {
+    let other = rebuilt.iter().find(|s| s.magic == section.magic);
+    if other.map(|s| s.data != section.data).unwrap_or(true) {
+      divergences.push(Divergence::Section {
+        magic: String::from_utf8_lossy(&section.magic).into_owned(),
+        original: Some(section.data.len()),
+        rebuilt: other.map(|s| s.data.len()),
+      });
+    }
+  }
+  for section in rebuilt.iter().filter(|s| !original.iter().any(|o| o.magic == s.magic)) {
+    divergences.push(Divergence::Section {
+      magic: String::from_utf8_lossy(&section.magic).into_owned(),
+      original: None,
+      rebuilt: Some(section.data.len()),
+    });
+  }
+}
+
+/// Compare two MSBTs section by section, reporting at most one TXT2 or ATR1 divergence per label.
+pub fn compare(original: &Msbt, rebuilt: &Msbt) -> Vec<Divergence> {
+  let mut divergences = Vec::new();
+
+  let (orig_lbl1, new_lbl1) = match (original.lbl1(), rebuilt.lbl1()) {
+    (Some(o), Some(n)) => (o, n),
+    _ => {
+      divergences.push(Divergence::MissingLbl1);
+      return divergences;
+    },
+  };
+
+  if orig_lbl1.group_count() != new_lbl1.group_count() {
+    divergences.push(Divergence::GroupCount {
+      original: orig_lbl1.group_count(),
+      rebuilt: new_lbl1.group_count(),
+    });
+  }
+
+  let attributes = |msbt: &Msbt, index: u32| msbt.atr1()
+    .and_then(|a| a.strings().get(index as usize).map(|s| crate::util::strip_nul(s).to_string()));
+
+  for label in orig_lbl1.labels() {
+    let new_label = match new_lbl1.labels().iter().find(|l| l.name() == label.name()) {
+      Some(l) => l,
+      None => {
+        divergences.push(Divergence::LabelMissing { label: label.name().to_string() });
+        continue;
+      },
+    };
+
+    let orig_value = label.value_raw().unwrap_or_default();
+    let new_value = new_label.value_raw().unwrap_or_default();
+    if orig_value != new_value {
+      let offset = orig_value.iter()
+        .zip(new_value.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| std::cmp::min(orig_value.len(), new_value.len()));
+      divergences.push(Divergence::Txt2 {
+        label: label.name().to_string(),
+        offset,
+        original: orig_value.get(offset).cloned(),
+        rebuilt: new_value.get(offset).cloned(),
+      });
+      continue;
+    }
+
+    let orig_attributes = attributes(original, label.index());
+    let new_attributes = attributes(rebuilt, new_label.index());
+    if orig_attributes != new_attributes {
+      divergences.push(Divergence::Atr1 {
+        label: label.name().to_string(),
+        original: orig_attributes,
+        rebuilt: new_attributes,
+      });
+    }
+  }
+
+  for label in new_lbl1.labels() {
+    if !orig_lbl1.labels().iter().any(|l| l.name() == label.name()) {
+      divergences.push(Divergence::LabelAdded { label: label.name().to_string() });
+    }
+  }
+
+  let orig_atr1_unknown = original.atr1().map(|a| a.unknown_1());
+  let new_atr1_unknown = rebuilt.atr1().map(|a| a.unknown_1());
+  if orig_atr1_unknown != new_atr1_unknown {
+    divergences.push(Divergence::Atr1Unknown {
+      original: orig_atr1_unknown,
+      rebuilt: new_atr1_unknown,
+    });
+  }
+
+  if original.ato1().map(|a| a.unknown_bytes()) != rebuilt.ato1().map(|a| a.unknown_bytes()) {
+    divergences.push(Divergence::Ato1);
+  }
+
+  if original.tsy1().map(|a| a.unknown_bytes()) != rebuilt.tsy1().map(|a| a.unknown_bytes()) {
+    divergences.push(Divergence::Tsy1);
+  }
+
+  // NLI1 points at TXT2 indices, which are allowed to move, so compare the labels they point to
+  let nli1_labels = |msbt: &Msbt| -> BTreeMap<u32, Option<String>> {
+    let lbl1 = msbt.lbl1();
+    msbt.nli1()
+      .map(|n| n.global_ids()
+        .iter()
+        .map(|(&id, &index)| (id, lbl1.and_then(|l| l.labels()
+          .iter()
+          .find(|l| l.index() == index)
+          .map(|l| l.name().to_string()))))
+        .collect())
+      .unwrap_or_default()
+  };
+  let orig_nli1 = nli1_labels(original);
+  let new_nli1 = nli1_labels(rebuilt);
+  for (&id, orig_label) in &orig_nli1 {
+    let new_label = new_nli1.get(&id).cloned().unwrap_or(None);
+    if *orig_label != new_label {
+      divergences.push(Divergence::Nli1 {
+        global_id: id,
+        original: orig_label.clone(),
+        rebuilt: new_label,
+      });
+    }
+  }
+  for (&id, new_label) in &new_nli1 {
+    if !orig_nli1.contains_key(&id) {
+      divergences.push(Divergence::Nli1 {
+        global_id: id,
+        original: None,
+        rebuilt: new_label.clone(),
+      });
+    }
+  }
+
+  divergences
+}
@@ -8,31 +8,14 @@ shopt -s globstar
 
 install_package() {
   if [ ! -x "$(command -v "$1")" ] && [ "$UID" -eq 0 ] && [ -x "$(command -v apt-get)" ]; then
-    echo "installing gnu parallel"
+    echo "installing $2"
     apt-get install --no-install-recommends -qqq -y "$2"
   elif [ ! -x "$(command -v "$1")" ] && [ "$UID" -ne 0 ]; then
-    echo "missing gnu parallel. exiting."
+    echo "missing $1. exiting."
     exit 1
   fi
 }
 
-check_diff() {
-  set +e
-  name=$(echo "$1" | cut -d'/' -f2-)
-  if ! res="$(func_equiv "$1" "$2"/"$name")"; then
-    echo "$name: msbts are NOT functionally equivalent"
-    echo "$res"
-    exit 1
-  fi
-  echo "$name: $res"
-  set -e
-}
-
-check_diffs() {
-  export -f check_diff
-  parallel 'check_diff {1} {2}' ::: "$1"/**/*.msbt ::: "$2"
-}
-
 main() {
   [[ "$CI_JOB_ID" != "" ]] && cp target/release/msyt /usr/local/bin/
 
@@ -46,17 +29,8 @@ main() {
   cd "$tmp" || exit
 
   install_package wget wget
-  install_package parallel parallel
   install_package xz xz-utils
 
-  if [ ! -x "$(command -v func_equiv)" ]; then
-    echo "installing func_equiv"
-    wget https://kyleclemens.com/assets/msyt/func_equiv.xz
-    xz -d func_equiv.xz
-    chmod +x func_equiv
-    mv func_equiv /usr/local/bin/
-  fi
-
   echo "downloading vanilla msbts"
 
   # download vanilla switch msbts
@@ -93,11 +67,11 @@ main() {
 
   # check that switch msbts are functionally equivalent to vanilla
   echo "comparing switch msbts"
-  check_diffs "msbt_switch" "switch"
+  msyt verify -d -c msbt_switch switch
 
   # check that wiiu msbts are functionally equivalent to vanilla
   echo "comparing wiiu msbts"
-  check_diffs "msbt_wiiu" "wiiu"
+  msyt verify -d -c msbt_wiiu wiiu
 
   echo "done"
 }